@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use std::io::Result;
+
+use crate::screen_buffer::{Coordinate, GameContent, ScreenBuffer};
+use crate::snake::add_snake_to_buffer;
+
+/// Time between two rendered frames, independent of the speed of the snakes.
+pub const ANIMATION_FRAME_TIME: Duration = Duration::from_millis(33);
+
+const BLINK_DURATION: Duration = Duration::from_millis(900);
+const BLINK_INTERVAL: Duration = Duration::from_millis(150);
+const CRASH_FLASH_DURATION: Duration = Duration::from_millis(800);
+const CRASH_FLASH_INTERVAL: Duration = Duration::from_millis(100);
+const CRASH_FADE_DURATION: Duration = Duration::from_millis(600);
+
+enum AnimationKind {
+    /// blinks newly spawned content at the given position
+    Blink {
+        pos: Coordinate,
+        content: GameContent,
+    },
+    /// flashes a crashed snake, then lets it dissolve from the tail to the head
+    Crash {
+        body: Vec<Coordinate>,
+        team_idx: usize,
+    },
+}
+
+struct Animation {
+    kind: AnimationKind,
+    start: Instant,
+}
+
+impl Animation {
+    fn duration(&self) -> Duration {
+        match self.kind {
+            AnimationKind::Blink { .. } => BLINK_DURATION,
+            AnimationKind::Crash { .. } => CRASH_FLASH_DURATION + CRASH_FADE_DURATION,
+        }
+    }
+
+    /// Cells the animation may draw on.
+    fn cells(&self) -> &[Coordinate] {
+        match &self.kind {
+            AnimationKind::Blink { pos, .. } => std::slice::from_ref(pos),
+            AnimationKind::Crash { body, .. } => body,
+        }
+    }
+
+    fn apply(&self, screen_buffer: &mut ScreenBuffer, elapsed: Duration) {
+        match &self.kind {
+            AnimationKind::Blink { pos, content } => {
+                let is_hidden = (elapsed.as_millis() / BLINK_INTERVAL.as_millis()) % 2 == 1;
+                // the content might have been eaten in the meantime
+                if is_hidden && screen_buffer.get_at(pos.row, pos.col) == *content {
+                    screen_buffer.set_at(pos.row, pos.col, GameContent::Empty);
+                }
+            }
+            AnimationKind::Crash { body, team_idx } => {
+                let num_visible = if elapsed < CRASH_FLASH_DURATION {
+                    let is_visible =
+                        (elapsed.as_millis() / CRASH_FLASH_INTERVAL.as_millis()).is_multiple_of(2);
+                    if is_visible {
+                        body.len()
+                    } else {
+                        0
+                    }
+                } else {
+                    let fade_progress = (elapsed - CRASH_FLASH_DURATION).as_secs_f64()
+                        / CRASH_FADE_DURATION.as_secs_f64();
+                    (body.len() as f64 * (1.0 - fade_progress)).ceil() as usize
+                };
+                if num_visible > 0 {
+                    add_snake_to_buffer(screen_buffer, &body[..num_visible], *team_idx);
+                }
+            }
+        }
+    }
+}
+
+/// Short animations drawn on top of the game, driven by their own clock instead of the
+/// game loop, so that they stay smooth no matter how fast the snakes move.
+pub struct Animations {
+    running: Vec<Animation>,
+}
+
+impl Animations {
+    pub fn new() -> Animations {
+        Animations { running: vec![] }
+    }
+
+    pub fn blink(&mut self, pos: Coordinate, content: GameContent) {
+        self.start(AnimationKind::Blink { pos, content });
+    }
+
+    pub fn crash(&mut self, body: &[Coordinate], team_idx: usize) {
+        self.start(AnimationKind::Crash {
+            body: body.to_vec(),
+            team_idx,
+        });
+    }
+
+    fn start(&mut self, kind: AnimationKind) {
+        self.running.push(Animation {
+            kind,
+            start: Instant::now(),
+        });
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Draws the current frame of all animations on top of the screen buffer.
+    /// Animations that are over are dropped.
+    pub fn apply(&mut self, screen_buffer: &mut ScreenBuffer, now: Instant) {
+        self.running
+            .retain(|animation| now.duration_since(animation.start) < animation.duration());
+        for animation in &self.running {
+            animation.apply(screen_buffer, now.duration_since(animation.start));
+        }
+    }
+
+    /// Draws the screen buffer with the current state of all animations on top. The
+    /// cells below the animations are restored afterwards, so that the screen buffer
+    /// keeps the frame without animations and only the cells they cover are drawn.
+    pub fn draw_frame(
+        &mut self,
+        screen_buffer: &mut ScreenBuffer,
+        stdout: &mut std::io::Stdout,
+    ) -> Result<()> {
+        let covered: Vec<(Coordinate, GameContent)> = self
+            .running
+            .iter()
+            .flat_map(|animation| animation.cells())
+            .map(|pos| (*pos, screen_buffer.get_at(pos.row, pos.col)))
+            .collect();
+        self.apply(screen_buffer, Instant::now());
+        let result = screen_buffer.draw(stdout);
+        for (pos, content) in covered {
+            screen_buffer.set_at(pos.row, pos.col, content);
+        }
+        result
+    }
+}
+
+/// Renders a bar that shrinks while the countdown runs, e.g. "[=======   ]".
+pub fn countdown_bar(remaining: Duration, total: Duration, width: usize) -> String {
+    let filled = ((remaining.as_secs_f64() / total.as_secs_f64()) * width as f64).ceil() as usize;
+    let filled = filled.min(width);
+    format!("[{}{}]", "=".repeat(filled), " ".repeat(width - filled))
+}
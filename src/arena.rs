@@ -0,0 +1,396 @@
+use std::cmp::Reverse;
+use std::mem;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::hazard::Hazard;
+use crate::level::Level;
+use crate::screen_buffer::{Coordinate, GameContent};
+use crate::snake::Direction;
+
+/// Where new food appears.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpawnPolicy {
+    /// anywhere in the arena
+    Uniform,
+    /// far away from the heads of the snakes
+    AwayFromHeads,
+    /// close to the center of the arena
+    NearCenter,
+    /// next to other food, so that several pieces can be eaten in a row
+    Clustered,
+}
+
+/// Share of the free cells the non-uniform spawn policies choose from, the ones
+/// they like best.
+const PREFERRED_SHARE: f64 = 0.1;
+/// Clustered food lands at most this many steps away from other food.
+const CLUSTER_RADIUS: usize = 3;
+/// Random patrols and mines appear at least this many steps away from the heads of the snakes.
+const HAZARD_MIN_DISTANCE: usize = 5;
+
+/// Kinds of food, differing in how much a snake grows and how many points it earns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FoodKind {
+    Regular,
+    /// rare, but makes the snake grow a lot and is worth many points
+    Golden,
+}
+
+impl FoodKind {
+    /// Number of segments the snake grows by, given the growth of regular food.
+    pub fn growth(self, base_growth: usize) -> usize {
+        match self {
+            FoodKind::Regular => base_growth,
+            FoodKind::Golden => 3 * base_growth,
+        }
+    }
+
+    pub fn points(self) -> usize {
+        match self {
+            FoodKind::Regular => 1,
+            FoodKind::Golden => 5,
+        }
+    }
+
+    pub fn content(self) -> GameContent {
+        match self {
+            FoodKind::Regular => GameContent::Food,
+            FoodKind::Golden => GameContent::GoldenFood,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Food {
+    pub pos: Coordinate,
+    pub kind: FoodKind,
+    /// tick at which the food relocates if nobody ate it, `None` if it stays forever
+    pub expires_at_tick: Option<u64>,
+}
+
+/// Everything in the arena apart from the snakes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arena {
+    pub width: usize,
+    pub height: usize,
+    pub food: Vec<Food>,
+    /// walls of the level and leftovers of eliminated snakes
+    pub obstacles: Vec<Coordinate>,
+    pub wreck_food: Vec<Coordinate>,
+    /// the two ends of every portal, a snake entering one end leaves through the other
+    pub portals: Vec<(Coordinate, Coordinate)>,
+    pub hazards: Vec<Hazard>,
+    /// number of rings the border has advanced inwards by shrinking
+    pub inset: usize,
+    /// tick at which the arena shrinks next, `None` if it never does
+    pub next_shrink_tick: Option<u64>,
+}
+
+impl Arena {
+    pub fn new(width: usize, height: usize) -> Arena {
+        Arena {
+            width,
+            height,
+            food: vec![],
+            obstacles: vec![],
+            wreck_food: vec![],
+            portals: vec![],
+            hazards: vec![],
+            inset: 0,
+            next_shrink_tick: None,
+        }
+    }
+
+    pub fn from_level(level: &Level) -> Arena {
+        let mut hazards: Vec<Hazard> = level
+            .patrols
+            .iter()
+            .map(|&(pos, direction)| Hazard::Patrol { pos, direction })
+            .collect();
+        if !level.gates.is_empty() {
+            hazards.push(Hazard::gate(level.gates.clone(), 0));
+        }
+        Arena {
+            obstacles: level.walls.clone(),
+            portals: level.portals.clone(),
+            hazards,
+            ..Arena::new(level.width, level.height)
+        }
+    }
+
+    pub fn is_portal_at(&self, pos: &Coordinate) -> bool {
+        self.portal_exit(pos).is_some()
+    }
+
+    /// Returns where a snake entering the portal at the given position comes out.
+    pub fn portal_exit(&self, pos: &Coordinate) -> Option<Coordinate> {
+        self.portals.iter().find_map(|(first, second)| {
+            if first == pos {
+                Some(*second)
+            } else if second == pos {
+                Some(*first)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Adds portals with both ends on random free cells.
+    pub fn place_random_portals<R: Rng>(
+        &mut self,
+        count: usize,
+        snake_cells: &[Coordinate],
+        rng: &mut R,
+    ) {
+        for _ in 0..count {
+            let free_cells = self.free_cells(snake_cells);
+            if let [first, second] = free_cells.choose_multiple(rng, 2).collect::<Vec<_>>()[..] {
+                self.portals.push((*first, *second));
+            }
+        }
+    }
+
+    /// Adds blocks patrolling in random directions on random free cells.
+    pub fn place_random_patrols<R: Rng>(
+        &mut self,
+        count: usize,
+        snake_cells: &[Coordinate],
+        heads: &[Coordinate],
+        rng: &mut R,
+    ) {
+        let directions = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ];
+        for _ in 0..count {
+            if let (Some(pos), Some(direction)) = (
+                self.random_hazard_cell(snake_cells, heads, rng),
+                directions.choose(rng),
+            ) {
+                self.hazards.push(Hazard::Patrol {
+                    pos,
+                    direction: *direction,
+                });
+            }
+        }
+    }
+
+    pub fn is_hazard_at(&self, pos: &Coordinate) -> bool {
+        self.hazards
+            .iter()
+            .any(|hazard| hazard.cells().contains(pos))
+    }
+
+    pub fn is_deadly_at(&self, pos: &Coordinate, tick: u64) -> bool {
+        self.obstacles.contains(pos)
+            || self
+                .hazards
+                .iter()
+                .any(|hazard| hazard.is_deadly_at(pos, tick))
+    }
+
+    /// Moves the patrols, opens and closes the gates and replaces vanished mines,
+    /// so that the given number of mines is in the arena.
+    pub fn update_hazards<R: Rng>(
+        &mut self,
+        tick: u64,
+        snake_cells: &[Coordinate],
+        heads: &[Coordinate],
+        mine_count: usize,
+        rng: &mut R,
+    ) {
+        let mut hazards = mem::take(&mut self.hazards);
+        hazards.retain(|hazard| !hazard.has_vanished(tick));
+
+        // cells a patrol may not enter and a gate may not close on
+        let mut taken: Vec<Coordinate> = snake_cells
+            .iter()
+            .chain(self.food.iter().map(|food| &food.pos))
+            .chain(&self.obstacles)
+            .chain(&self.wreck_food)
+            .chain(
+                self.portals
+                    .iter()
+                    .flat_map(|(first, second)| [first, second]),
+            )
+            .copied()
+            .collect();
+        let hazard_cells: Vec<Vec<Coordinate>> = hazards.iter().map(Hazard::cells).collect();
+        for (idx, hazard) in hazards.iter_mut().enumerate() {
+            let other_hazard_cells = hazard_cells
+                .iter()
+                .enumerate()
+                .filter(|&(other_idx, _)| other_idx != idx)
+                .flat_map(|(_, cells)| cells);
+            let blocked: Vec<Coordinate> =
+                taken.iter().chain(other_hazard_cells).copied().collect();
+            hazard.update(tick, |pos| self.is_inside(pos) && !blocked.contains(pos));
+            // later patrols must not move onto this one
+            taken.extend(hazard.cells());
+        }
+        self.hazards = hazards;
+
+        let num_mines = self
+            .hazards
+            .iter()
+            .filter(|hazard| matches!(hazard, Hazard::Mine { .. }))
+            .count();
+        for _ in num_mines..mine_count {
+            match self.random_hazard_cell(snake_cells, heads, rng) {
+                Some(pos) => self.hazards.push(Hazard::mine(pos, tick)),
+                None => break,
+            }
+        }
+    }
+
+    /// Returns a random free cell not too close to the heads of the snakes.
+    fn random_hazard_cell<R: Rng>(
+        &self,
+        snake_cells: &[Coordinate],
+        heads: &[Coordinate],
+        rng: &mut R,
+    ) -> Option<Coordinate> {
+        self.free_cells(snake_cells)
+            .into_iter()
+            .filter(|cell| nearest_distance(cell, heads) >= HAZARD_MIN_DISTANCE)
+            .collect::<Vec<_>>()
+            .choose(rng)
+            .copied()
+    }
+
+    /// Whether the position lies within the border, which may have advanced by shrinking.
+    pub fn is_inside(&self, pos: &Coordinate) -> bool {
+        is_inside_border(pos, self.width, self.height, self.inset)
+    }
+
+    /// Advances the border by one ring, removing everything the new ring covers.
+    pub fn shrink(&mut self) {
+        self.inset += 1;
+        let (width, height, inset) = (self.width, self.height, self.inset);
+        let is_inside = |pos: &Coordinate| is_inside_border(pos, width, height, inset);
+        self.food.retain(|food| is_inside(&food.pos));
+        self.obstacles.retain(is_inside);
+        self.wreck_food.retain(is_inside);
+        self.portals
+            .retain(|(first, second)| is_inside(first) && is_inside(second));
+        self.hazards
+            .retain(|hazard| hazard.cells().iter().all(is_inside));
+    }
+
+    pub fn is_food_at(&self, pos: &Coordinate) -> bool {
+        self.food.iter().any(|food| food.pos == *pos) || self.wreck_food.contains(pos)
+    }
+
+    /// Cells inside the border that are not taken by the snakes or anything else.
+    pub fn free_cells(&self, snake_cells: &[Coordinate]) -> Vec<Coordinate> {
+        let mut is_taken = vec![vec![false; self.width]; self.height];
+        let taken = snake_cells
+            .iter()
+            .chain(self.food.iter().map(|food| &food.pos))
+            .chain(&self.obstacles)
+            .chain(&self.wreck_food)
+            .chain(
+                self.portals
+                    .iter()
+                    .flat_map(|(first, second)| [first, second]),
+            );
+        let hazard_cells: Vec<Coordinate> = self.hazards.iter().flat_map(Hazard::cells).collect();
+        for pos in taken.chain(&hazard_cells) {
+            if pos.row < self.height && pos.col < self.width {
+                is_taken[pos.row][pos.col] = true;
+            }
+        }
+
+        (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |col| Coordinate { row, col }))
+            .filter(|pos| self.is_inside(pos) && !is_taken[pos.row][pos.col])
+            .collect()
+    }
+
+    /// Places a new piece of food on a free cell chosen by the spawn policy.
+    /// Returns its position, or `None` if there is no free cell left.
+    pub fn spawn_food<R: Rng>(
+        &mut self,
+        kind: FoodKind,
+        policy: SpawnPolicy,
+        snake_cells: &[Coordinate],
+        heads: &[Coordinate],
+        expires_at_tick: Option<u64>,
+        rng: &mut R,
+    ) -> Option<Coordinate> {
+        let free_cells = self.free_cells(snake_cells);
+        let food_cells: Vec<Coordinate> = self.food.iter().map(|food| food.pos).collect();
+        let center = Coordinate {
+            row: self.height / 2,
+            col: self.width / 2,
+        };
+
+        let candidates = match policy {
+            SpawnPolicy::Uniform => free_cells.clone(),
+            SpawnPolicy::AwayFromHeads => most_preferred(&free_cells, rng, |cell| {
+                Reverse(nearest_distance(cell, heads))
+            }),
+            SpawnPolicy::NearCenter => {
+                most_preferred(&free_cells, rng, |cell| distance(cell, &center))
+            }
+            SpawnPolicy::Clustered => free_cells
+                .iter()
+                .filter(|cell| nearest_distance(cell, &food_cells) <= CLUSTER_RADIUS)
+                .copied()
+                .collect(),
+        };
+        // e.g. the first piece of clustered food can go anywhere
+        let candidates = if candidates.is_empty() {
+            free_cells
+        } else {
+            candidates
+        };
+
+        let pos = *candidates.choose(rng)?;
+        self.food.push(Food {
+            pos,
+            kind,
+            expires_at_tick,
+        });
+        Some(pos)
+    }
+}
+
+/// Whether the position lies within a border that is `inset + 1` cells thick.
+fn is_inside_border(pos: &Coordinate, width: usize, height: usize, inset: usize) -> bool {
+    pos.row > inset
+        && pos.col > inset
+        && pos.row + 1 + inset < height
+        && pos.col + 1 + inset < width
+}
+
+/// Returns the share of the cells with the smallest keys, ties are broken randomly.
+fn most_preferred<R: Rng, K: Ord>(
+    cells: &[Coordinate],
+    rng: &mut R,
+    key: impl Fn(&Coordinate) -> K,
+) -> Vec<Coordinate> {
+    let mut cells = cells.to_vec();
+    cells.shuffle(rng);
+    cells.sort_by_cached_key(key);
+    let num_preferred = ((cells.len() as f64 * PREFERRED_SHARE).ceil() as usize).max(1);
+    cells.truncate(num_preferred);
+    cells
+}
+
+/// Number of steps a snake needs from one cell to the other.
+fn distance(a: &Coordinate, b: &Coordinate) -> usize {
+    a.row.abs_diff(b.row) + a.col.abs_diff(b.col)
+}
+
+fn nearest_distance(cell: &Coordinate, others: &[Coordinate]) -> usize {
+    others
+        .iter()
+        .map(|other| distance(cell, other))
+        .min()
+        .unwrap_or(usize::MAX)
+}
@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::level::Level;
+use crate::scores::unix_time;
+use crate::screen_buffer::Coordinate;
+use crate::snake::Direction;
+
+pub const DAILY_TIME_LIMIT: Duration = Duration::from_secs(60);
+pub const DAILY_MINES: usize = 2;
+pub const DAILY_GOLDEN_FOOD_CHANCE: f64 = 0.1;
+const DAILY_WALLS: usize = 8;
+const DAILY_PORTALS: usize = 2;
+const DAILY_PATROLS: usize = 2;
+const MIN_WALL_LENGTH: usize = 3;
+const MAX_WALL_LENGTH: usize = 8;
+/// Walls keep this many cells away from the start position and the way ahead of it.
+const START_CLEARANCE: usize = 2;
+/// Patrols start at least this many steps away from the head of the snake.
+const PATROL_MIN_DISTANCE: usize = 8;
+
+/// The challenge of the day: a time-attack round on a board generated from the date,
+/// which is the same for everyone playing on that day.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DailyChallenge {
+    /// UTC date of the challenge, e.g. "2024-02-29"
+    pub date: String,
+    /// seeds every random decision of the challenge
+    pub seed: u64,
+}
+
+impl DailyChallenge {
+    pub fn today() -> DailyChallenge {
+        let date = format_date(unix_time());
+        DailyChallenge {
+            seed: fnv1a(date.as_bytes()),
+            date,
+        }
+    }
+
+    /// Name of the high score table holding the official result of the day.
+    pub fn score_table(&self) -> String {
+        format!("daily-{}", self.date)
+    }
+
+    /// Generates the walls, portals and patrols of the day. The cells in `start`, the
+    /// snake at its start position heading up, and the way ahead of it stay clear.
+    pub fn level(&self, width: usize, height: usize, start: &[Coordinate]) -> Level {
+        let mut rng = ChaCha12Rng::seed_from_u64(self.seed);
+        let head = start[0];
+        let tail_row = start.iter().map(|pos| pos.row).max().unwrap_or(head.row);
+        let is_reserved = |pos: &Coordinate| {
+            pos.col.abs_diff(head.col) <= START_CLEARANCE && pos.row <= tail_row + START_CLEARANCE
+        };
+
+        let mut walls = vec![];
+        for _ in 0..DAILY_WALLS {
+            let length = rng.gen_range(MIN_WALL_LENGTH..=MAX_WALL_LENGTH);
+            let direction = *[Direction::Right, Direction::Down]
+                .choose(&mut rng)
+                .unwrap();
+            // walls keep a gap to the border, so that the snake can pass on both sides
+            let first = Coordinate {
+                row: rng.gen_range(3..height - 3),
+                col: rng.gen_range(3..width - 3),
+            };
+            let wall: Vec<Coordinate> = (0..length)
+                .map(|offset| match direction {
+                    Direction::Down => Coordinate {
+                        row: first.row + offset,
+                        col: first.col,
+                    },
+                    _ => Coordinate {
+                        row: first.row,
+                        col: first.col + offset,
+                    },
+                })
+                .take_while(|pos| pos.row < height - 3 && pos.col < width - 3)
+                .collect();
+            if !wall.iter().any(is_reserved) {
+                walls.extend(wall);
+            }
+        }
+
+        let mut free_cells: Vec<Coordinate> = (1..height - 1)
+            .flat_map(|row| (1..width - 1).map(move |col| Coordinate { row, col }))
+            .filter(|pos| !walls.contains(pos) && !is_reserved(pos))
+            .collect();
+        free_cells.shuffle(&mut rng);
+
+        let mut portals = vec![];
+        for _ in 0..DAILY_PORTALS {
+            if let (Some(first), Some(second)) = (free_cells.pop(), free_cells.pop()) {
+                portals.push((first, second));
+            }
+        }
+
+        free_cells.retain(|pos| {
+            pos.row.abs_diff(head.row) + pos.col.abs_diff(head.col) >= PATROL_MIN_DISTANCE
+        });
+        let patrols = free_cells
+            .iter()
+            .take(DAILY_PATROLS)
+            .map(|&pos| {
+                let direction = *[Direction::Right, Direction::Down]
+                    .choose(&mut rng)
+                    .unwrap();
+                (pos, direction)
+            })
+            .collect();
+
+        Level {
+            width,
+            height,
+            walls,
+            portals,
+            gates: vec![],
+            patrols,
+        }
+    }
+
+    /// One line describing the result, to be shared with the others playing today.
+    pub fn summary(&self, score: usize, length: usize, ending: &str) -> String {
+        format!(
+            "rs_snake daily {}: {} points, length {}, {}",
+            self.date, score, length, ending
+        )
+    }
+}
+
+/// 64 bit FNV-1a hash, which unlike the hashers of the standard library is the same
+/// on every platform and in every version.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Formats seconds since the unix epoch as a UTC date, e.g. "2024-02-29".
+fn format_date(unix_time: u64) -> String {
+    // civil from days, see https://howardhinnant.github.io/date_algorithms.html
+    let days = (unix_time / 86400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snake::Snake;
+
+    fn cells(cells: &[(usize, usize)]) -> Vec<Coordinate> {
+        cells
+            .iter()
+            .map(|&(row, col)| Coordinate { row, col })
+            .collect()
+    }
+
+    /// The cells of a straight wall of `length` cells starting at `(row, col)`.
+    fn wall((row, col): (usize, usize), length: usize, direction: Direction) -> Vec<Coordinate> {
+        let positions: Vec<(usize, usize)> = (0..length)
+            .map(|offset| match direction {
+                Direction::Down => (row + offset, col),
+                _ => (row, col + offset),
+            })
+            .collect();
+        cells(&positions)
+    }
+
+    /// Everyone playing on the same day must get the same board, on every platform and
+    /// with every build, so the whole chain from the clock to the level is pinned here.
+    #[test]
+    fn boards_of_the_day_stay_the_same() {
+        assert_eq!(format_date(1_709_164_800), "2024-02-29");
+        assert_eq!(format_date(1_709_251_199), "2024-02-29");
+        assert_eq!(format_date(1_709_251_200), "2024-03-01");
+
+        let date = format_date(1_709_164_800);
+        let daily = DailyChallenge {
+            seed: fnv1a(date.as_bytes()),
+            date,
+        };
+        assert_eq!(daily.seed, 0xb82e_8982_d744_d56c);
+
+        let level = daily.level(40, 40, &Snake::new(0, 40, 40).body_pos);
+        let walls = [
+            wall((9, 14), 5, Direction::Down),
+            wall((31, 25), 8, Direction::Right),
+            wall((34, 26), 5, Direction::Right),
+            wall((24, 23), 3, Direction::Right),
+            wall((8, 20), 8, Direction::Down),
+        ]
+        .concat();
+        let portal_ends = cells(&[(6, 18), (9, 25), (15, 22), (30, 20)]);
+        let patrol_starts = cells(&[(17, 2), (33, 33)]);
+        assert_eq!(
+            level,
+            Level {
+                width: 40,
+                height: 40,
+                walls,
+                portals: vec![
+                    (portal_ends[0], portal_ends[1]),
+                    (portal_ends[2], portal_ends[3])
+                ],
+                gates: vec![],
+                patrols: vec![
+                    (patrol_starts[0], Direction::Right),
+                    (patrol_starts[1], Direction::Right)
+                ],
+            }
+        );
+    }
+}
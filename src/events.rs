@@ -1,69 +1,232 @@
-use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::io::{Error, ErrorKind, Result};
+use std::mem;
+use std::time::Instant;
 
-use std::io::Result;
+use async_std::task;
+use crossterm::event::{
+    Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind,
+};
+use futures::future::{self, Either};
+use futures::{FutureExt, Stream, StreamExt};
+use futures_timer::Delay;
 
-use crossterm::event::{poll, read, Event, KeyEvent};
+/// Terminal input the game reacts to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    /// a key was pressed, see [`pressed_key`]
+    Key(KeyCode),
+    /// Ctrl+C or Ctrl+D was pressed, which the terminal does not turn into a signal
+    /// in raw mode
+    Interrupt,
+    /// the terminal was resized and has to be redrawn completely
+    Resize,
+    FocusLost,
+    FocusGained,
+    /// the left mouse button was pressed or dragged at the given terminal position
+    Pointer {
+        column: u16,
+        line: u16,
+    },
+}
 
-#[derive(Clone)]
-pub struct KeyEventQueue<T: Send + Copy> {
-    inner: Arc<Mutex<VecDeque<T>>>,
+/// Reads terminal input asynchronously, so that the game wakes up as soon as input
+/// arrives and sleeps otherwise instead of polling for it.
+pub struct InputEvents<S = EventStream> {
+    stream: S,
+    received: Vec<GameEvent>,
 }
 
-impl<T: Send + Copy> KeyEventQueue<T> {
+impl InputEvents {
     pub fn new() -> Self {
+        Self::from_stream(EventStream::new())
+    }
+}
+
+impl<S: Stream<Item = Result<Event>> + Unpin> InputEvents<S> {
+    fn from_stream(stream: S) -> Self {
         Self {
-            inner: Arc::new(Mutex::new(VecDeque::new())),
+            stream,
+            received: vec![],
         }
     }
 
-    pub fn get_latest_event(&self) -> Option<T> {
-        let maybe_queue = self.inner.lock();
+    /// Waits until input arrives or the deadline has passed, whichever comes first.
+    /// Input that is already waiting is taken in any case, so that frames running late
+    /// can't hold it back.
+    pub fn wait_until(&mut self, deadline: Instant) -> Result<()> {
+        let now = Instant::now();
+        if now < deadline {
+            let next_event = task::block_on(async {
+                match future::select(self.stream.next(), Delay::new(deadline - now)).await {
+                    Either::Left((event, _)) => Some(event),
+                    Either::Right(_) => None,
+                }
+            });
+            if let Some(event) = next_event {
+                self.receive(event.ok_or_else(input_closed)??);
+            }
+        }
 
-        if let Ok(mut queue) = maybe_queue {
-            let el = queue.pop_back();
-            queue.clear();
-            el
-        } else {
-            panic!("poisoned mutex");
+        while let Some(event) = self.stream.next().now_or_never() {
+            self.receive(event.ok_or_else(input_closed)??);
         }
+        Ok(())
     }
 
-    pub fn get_all_events(&self) -> Option<Vec<T>> {
-        let maybe_queue = self.inner.lock();
+    fn receive(&mut self, event: Event) {
+        let game_event = match event {
+            Event::Key(event) if is_interrupt(&event) => GameEvent::Interrupt,
+            Event::Key(event) => match pressed_key(&event) {
+                Some(key_code) => GameEvent::Key(key_code),
+                None => return,
+            },
+            Event::Resize(_width, _height) => GameEvent::Resize,
+            Event::FocusLost => GameEvent::FocusLost,
+            Event::FocusGained => GameEvent::FocusGained,
+            Event::Mouse(event) => match event.kind {
+                MouseEventKind::Down(MouseButton::Left)
+                | MouseEventKind::Drag(MouseButton::Left) => GameEvent::Pointer {
+                    column: event.column,
+                    line: event.row,
+                },
+                _ => return,
+            },
+            Event::Paste(_) => return,
+        };
+        self.received.push(game_event);
+    }
 
-        if let Ok(mut queue) = maybe_queue {
-            let drained = queue.drain(..).collect::<Vec<_>>();
-            queue.clear();
-            Some(drained)
-        } else {
-            panic!("poisoned mutex");
-        }
+    /// Returns all input received since the last call.
+    pub fn take_all(&mut self) -> Vec<GameEvent> {
+        mem::take(&mut self.received)
     }
+}
 
-    fn add_event(&self, event: T) -> usize {
-        if let Ok(mut queue) = self.inner.lock() {
-            queue.push_back(event);
-            queue.len()
-        } else {
-            panic!("poisoned mutex");
-        }
+fn input_closed() -> Error {
+    Error::new(ErrorKind::UnexpectedEof, "the terminal input was closed")
+}
+
+fn is_interrupt(key_event: &KeyEvent) -> bool {
+    key_event.kind == KeyEventKind::Press
+        && key_event.modifiers.contains(KeyModifiers::CONTROL)
+        && matches!(key_event.code, KeyCode::Char('c' | 'C' | 'd' | 'D'))
+}
+
+/// Reduces a key event to the key that was pressed. Releases, repeats and Shift are
+/// dropped, terminals using the kitty keyboard protocol and Windows report those as
+/// well. Keys held together with Ctrl or Alt are shortcuts and ignored.
+fn pressed_key(key_event: &KeyEvent) -> Option<KeyCode> {
+    if key_event.kind != KeyEventKind::Press
+        || key_event
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        return None;
+    }
+    match key_event.code {
+        KeyCode::Char(c) => Some(KeyCode::Char(c.to_ascii_lowercase())),
+        key_code => Some(key_code),
     }
 }
 
-pub fn send_events(event_queue: &KeyEventQueue<KeyEvent>) -> Result<()> {
-    loop {
-        if poll(Duration::from_millis(3))? {
-            match read()? {
-                // will not block
-                Event::Key(event) => {
-                    event_queue.add_event(event);
-                }
-                Event::Mouse(_event) => {}
-                Event::Resize(_width, _height) => {}
-                Event::FocusGained | Event::FocusLost | Event::Paste(_) => {}
-            }
-        }
+/// Steering keys of a player, no matter which keys they are bound to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SteeringKey {
+    Left,
+    Right,
+    Up,
+    Down,
+    TurnLeft,
+    TurnRight,
+}
+
+/// What the game does in response to a key press.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Exit,
+    /// save the round in progress to continue it later, then exit
+    SaveAndExit,
+    Steer {
+        player_idx: usize,
+        key: SteeringKey,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+    use futures::stream;
+    use std::time::Duration;
+
+    fn key_press(c: char) -> Result<Event> {
+        Ok(Event::Key(KeyEvent::new(
+            KeyCode::Char(c),
+            KeyModifiers::NONE,
+        )))
+    }
+
+    #[test]
+    fn queued_input_is_taken_after_the_deadline() {
+        let keys = stream::iter(vec![key_press('a'), key_press('D')]).chain(stream::pending());
+        let mut input = InputEvents::from_stream(keys);
+
+        input
+            .wait_until(Instant::now() - Duration::from_millis(100))
+            .unwrap();
+
+        assert_eq!(
+            input.take_all(),
+            vec![
+                GameEvent::Key(KeyCode::Char('a')),
+                GameEvent::Key(KeyCode::Char('d'))
+            ]
+        );
+    }
+
+    #[test]
+    fn ctrl_c_and_ctrl_d_interrupt() {
+        let ctrl = |c: char| {
+            Ok(Event::Key(KeyEvent::new(
+                KeyCode::Char(c),
+                KeyModifiers::CONTROL,
+            )))
+        };
+        let alt = |c: char| {
+            Ok(Event::Key(KeyEvent::new(
+                KeyCode::Char(c),
+                KeyModifiers::ALT,
+            )))
+        };
+        let keys = stream::iter(vec![
+            ctrl('c'),
+            ctrl('x'),
+            alt('a'),
+            ctrl('d'),
+            key_press('c'),
+        ])
+        .chain(stream::pending());
+        let mut input = InputEvents::from_stream(keys);
+
+        input.wait_until(Instant::now()).unwrap();
+
+        assert_eq!(
+            input.take_all(),
+            vec![
+                GameEvent::Interrupt,
+                GameEvent::Interrupt,
+                GameEvent::Key(KeyCode::Char('c'))
+            ]
+        );
+    }
+
+    #[test]
+    fn closed_input_is_an_error() {
+        let mut input = InputEvents::from_stream(stream::iter(vec![key_press('a')]));
+
+        let result = input.wait_until(Instant::now() + Duration::from_secs(1));
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(input.take_all(), vec![GameEvent::Key(KeyCode::Char('a'))]);
     }
 }
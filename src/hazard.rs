@@ -0,0 +1,177 @@
+use crate::screen_buffer::{Coordinate, GameContent};
+use crate::snake::Direction;
+
+/// Ticks a patrolling block waits between two steps, making it slower than the snakes.
+const PATROL_STEP_TICKS: u64 = 2;
+const GATE_OPEN_TICKS: u64 = 48;
+const GATE_CLOSED_TICKS: u64 = 32;
+/// Ticks a hazard blinks before it becomes deadly.
+const WARNING_TICKS: u64 = 16;
+/// Ticks a mine stays deadly before it vanishes.
+const MINE_ARMED_TICKS: u64 = 120;
+
+/// Something in the arena that changes every tick and is deadly at least part of the time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Hazard {
+    /// a block moving back and forth along a row or column, turning around at
+    /// anything in its way
+    Patrol {
+        pos: Coordinate,
+        direction: Direction,
+    },
+    /// cells that open and close periodically; a gate waits for the snakes inside to
+    /// leave before it closes
+    Gate {
+        cells: Vec<Coordinate>,
+        is_closed: bool,
+        next_switch_tick: u64,
+    },
+    /// appears with a warning and is deadly once armed, until it vanishes
+    Mine {
+        pos: Coordinate,
+        armed_at_tick: u64,
+        vanishes_at_tick: u64,
+    },
+}
+
+/// What a hazard means for the snakes at the moment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HazardState {
+    Harmless,
+    /// about to become deadly
+    Warning,
+    Deadly,
+}
+
+impl Hazard {
+    pub fn gate(cells: Vec<Coordinate>, tick: u64) -> Hazard {
+        Hazard::Gate {
+            cells,
+            is_closed: false,
+            next_switch_tick: tick + GATE_OPEN_TICKS,
+        }
+    }
+
+    pub fn mine(pos: Coordinate, tick: u64) -> Hazard {
+        Hazard::Mine {
+            pos,
+            armed_at_tick: tick + WARNING_TICKS,
+            vanishes_at_tick: tick + WARNING_TICKS + MINE_ARMED_TICKS,
+        }
+    }
+
+    /// Cells the hazard takes up, whether it is deadly right now or not.
+    pub fn cells(&self) -> Vec<Coordinate> {
+        match self {
+            Hazard::Patrol { pos, .. } | Hazard::Mine { pos, .. } => vec![*pos],
+            Hazard::Gate { cells, .. } => cells.clone(),
+        }
+    }
+
+    pub fn state(&self, tick: u64) -> HazardState {
+        match self {
+            Hazard::Patrol { .. } => HazardState::Deadly,
+            Hazard::Gate {
+                is_closed,
+                next_switch_tick,
+                ..
+            } => {
+                if *is_closed {
+                    HazardState::Deadly
+                } else if tick + WARNING_TICKS >= *next_switch_tick {
+                    HazardState::Warning
+                } else {
+                    HazardState::Harmless
+                }
+            }
+            Hazard::Mine { armed_at_tick, .. } => {
+                if tick >= *armed_at_tick {
+                    HazardState::Deadly
+                } else {
+                    HazardState::Warning
+                }
+            }
+        }
+    }
+
+    pub fn is_deadly_at(&self, pos: &Coordinate, tick: u64) -> bool {
+        self.state(tick) == HazardState::Deadly && self.cells().contains(pos)
+    }
+
+    /// How the hazard is drawn, `None` if it is invisible right now.
+    /// Hazards about to become deadly blink.
+    pub fn content(&self, tick: u64) -> Option<GameContent> {
+        match self.state(tick) {
+            HazardState::Deadly => Some(GameContent::Hazard),
+            HazardState::Warning if tick.is_multiple_of(2) => Some(GameContent::Hazard),
+            _ => None,
+        }
+    }
+
+    /// Advances the hazard to the given tick. `is_free` tells whether a cell is
+    /// free for a patrolling block or a closing gate.
+    pub fn update(&mut self, tick: u64, is_free: impl Fn(&Coordinate) -> bool) {
+        match self {
+            Hazard::Patrol { pos, direction } => {
+                if !tick.is_multiple_of(PATROL_STEP_TICKS) {
+                    return;
+                }
+                for next_direction in [*direction, direction.opposite()] {
+                    if let Some(next_pos) = step(pos, next_direction).filter(|p| is_free(p)) {
+                        *pos = next_pos;
+                        *direction = next_direction;
+                        return;
+                    }
+                }
+            }
+            Hazard::Gate {
+                cells,
+                is_closed,
+                next_switch_tick,
+            } => {
+                if tick < *next_switch_tick {
+                    return;
+                }
+                if *is_closed {
+                    *is_closed = false;
+                    *next_switch_tick = tick + GATE_OPEN_TICKS;
+                } else if cells.iter().all(&is_free) {
+                    *is_closed = true;
+                    *next_switch_tick = tick + GATE_CLOSED_TICKS;
+                }
+            }
+            Hazard::Mine { .. } => {}
+        }
+    }
+
+    pub fn has_vanished(&self, tick: u64) -> bool {
+        match self {
+            Hazard::Mine {
+                vanishes_at_tick, ..
+            } => tick >= *vanishes_at_tick,
+            _ => false,
+        }
+    }
+}
+
+/// Returns the neighbouring cell in the given direction.
+fn step(pos: &Coordinate, direction: Direction) -> Option<Coordinate> {
+    Some(match direction {
+        Direction::Up => Coordinate {
+            row: pos.row.checked_sub(1)?,
+            col: pos.col,
+        },
+        Direction::Down => Coordinate {
+            row: pos.row + 1,
+            col: pos.col,
+        },
+        Direction::Left => Coordinate {
+            row: pos.row,
+            col: pos.col.checked_sub(1)?,
+        },
+        Direction::Right => Coordinate {
+            row: pos.row,
+            col: pos.col + 1,
+        },
+    })
+}
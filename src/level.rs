@@ -0,0 +1,125 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crate::screen_buffer::Coordinate;
+use crate::snake::Direction;
+
+/// A hand-made arena layout with walls, portals and hazards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+    /// size of the arena including the border
+    pub width: usize,
+    pub height: usize,
+    pub walls: Vec<Coordinate>,
+    /// the two ends of every portal, a snake entering one end leaves through the other
+    pub portals: Vec<(Coordinate, Coordinate)>,
+    /// cells of the gate, which opens and closes as a whole
+    pub gates: Vec<Coordinate>,
+    /// start position and direction of every patrolling block
+    pub patrols: Vec<(Coordinate, Direction)>,
+}
+
+impl Level {
+    pub fn is_blocked(&self, pos: &Coordinate) -> bool {
+        self.walls.contains(pos)
+            || self.gates.contains(pos)
+            || self.patrols.iter().any(|(patrol, _)| patrol == pos)
+            || self
+                .portals
+                .iter()
+                .any(|(first, second)| first == pos || second == pos)
+    }
+}
+
+/// Loads a level file, which draws the arena inside the border row by row:
+///
+/// ```text
+/// ; lines starting with a semicolon are comments
+/// ..........
+/// ..a..##...
+/// .....##.a.
+/// ```
+///
+/// `#` is a wall, `.` or a space is free and two equal letters are the ends of a portal.
+/// `=` is part of the gate, `-` and `|` are blocks patrolling horizontally and vertically.
+/// The arena is as large as the drawing plus the border.
+pub fn load_level(path: &Path) -> Result<Level> {
+    let content = fs::read_to_string(path)?;
+    let invalid = |line_idx: usize, msg: String| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{}:{}: {}", path.display(), line_idx + 1, msg),
+        )
+    };
+
+    let mut rows: Vec<(usize, &str)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with(';'))
+        .collect();
+    while rows.last().is_some_and(|(_, line)| line.trim().is_empty()) {
+        rows.pop();
+    }
+
+    let mut walls = vec![];
+    let mut gates = vec![];
+    let mut patrols = vec![];
+    // positions of both ends of every portal, by letter
+    let mut portal_ends: Vec<(char, usize, Vec<Coordinate>)> = vec![];
+    for (row_idx, (line_idx, line)) in rows.iter().enumerate() {
+        for (col_idx, symbol) in line.trim_end().chars().enumerate() {
+            // the border takes up the first row and column
+            let pos = Coordinate {
+                row: row_idx + 1,
+                col: col_idx + 1,
+            };
+            match symbol {
+                '#' => walls.push(pos),
+                '=' => gates.push(pos),
+                '-' => patrols.push((pos, Direction::Right)),
+                '|' => patrols.push((pos, Direction::Down)),
+                '.' | ' ' => {}
+                'a'..='z' => match portal_ends
+                    .iter_mut()
+                    .find(|(letter, _, _)| *letter == symbol)
+                {
+                    Some((_, _, ends)) => ends.push(pos),
+                    None => portal_ends.push((symbol, *line_idx, vec![pos])),
+                },
+                _ => return Err(invalid(*line_idx, format!("unknown symbol '{}'", symbol))),
+            }
+        }
+    }
+
+    let mut portals = vec![];
+    for (letter, line_idx, ends) in portal_ends {
+        match ends.as_slice() {
+            [first, second] => portals.push((*first, *second)),
+            _ => {
+                return Err(invalid(
+                    line_idx,
+                    format!(
+                        "portal '{}' needs exactly two ends, found {}",
+                        letter,
+                        ends.len()
+                    ),
+                ))
+            }
+        }
+    }
+
+    let width = rows
+        .iter()
+        .map(|(_, line)| line.trim_end().chars().count())
+        .max()
+        .unwrap_or(0);
+    Ok(Level {
+        width: width + 2,
+        height: rows.len() + 2,
+        walls,
+        portals,
+        gates,
+        patrols,
+    })
+}
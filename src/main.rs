@@ -1,15 +1,62 @@
 extern crate clap;
-use clap::{Arg, ArgAction, Command};
+use clap::{error::ErrorKind, Arg, ArgAction, Command};
 
+mod animation;
+mod arena;
+mod daily;
 mod events;
+mod hazard;
+mod level;
+mod savegame;
+mod scores;
 mod screen_buffer;
 mod snake;
+mod terminal;
+mod theme;
 
-use snake::SnakeGame;
+use arena::SpawnPolicy;
+use crossterm::style::force_color_output;
+use daily::{DailyChallenge, DAILY_GOLDEN_FOOD_CHANCE, DAILY_MINES, DAILY_TIME_LIMIT};
+use level::load_level;
+use savegame::load_game;
+use screen_buffer::{is_color_supported, RenderMode};
+use snake::{
+    GameMode, GameSettings, Scoring, Snake, SnakeGame, SteeringMode, Wreckage, MAX_ARENA_HEIGHT,
+    MAX_ARENA_WIDTH, MAX_PLAYERS, MIN_ARENA_HEIGHT, MIN_ARENA_WIDTH, NORMAL_SPEED,
+};
 use std::io::Result;
+use std::path::PathBuf;
+use std::time::Duration;
+use theme::{load_themes, Theme, BUILTIN_THEME_NAMES};
+
+/// Options changing the rules of the game, which the daily challenge and a resumed game
+/// decide themselves.
+const GAMEPLAY_ARGS: [&str; 21] = [
+    "easy",
+    "hard",
+    "multiplayer",
+    "players",
+    "mode",
+    "time_limit",
+    "target_length",
+    "shrink_interval",
+    "wreckage",
+    "food",
+    "food_spawn",
+    "food_lifetime",
+    "golden_food",
+    "growth",
+    "scoring",
+    "teams",
+    "arena",
+    "level",
+    "portals",
+    "patrols",
+    "mines",
+];
 
 fn main() -> Result<()> {
-    let matches = Command::new("snake")
+    let mut cmd = Command::new("snake")
         .version("0.4.0")
         .author("Author: baurst")
         .about("Classic snake game for your terminal")
@@ -34,6 +81,234 @@ fn main() -> Result<()> {
                 .short('m')
                 .long("multi")
                 .help("enables multiplayer mode")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("players"),
+        )
+        .arg(
+            Arg::new("players")
+                .short('p')
+                .long("players")
+                .help("sets the number of players")
+                .value_parser(clap::value_parser!(u8).range(1..=MAX_PLAYERS as i64)),
+        )
+        .arg(
+            Arg::new("mode")
+                .long("mode")
+                .help("sets the game mode")
+                .value_parser(["classic", "elimination", "timed", "battle-royale", "time-attack", "target-length"])
+                .default_value("classic"),
+        )
+        .arg(
+            Arg::new("time_limit")
+                .long("time_limit")
+                .help("round duration in seconds for timed and time-attack mode")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("120"),
+        )
+        .arg(
+            Arg::new("target_length")
+                .long("target_length")
+                .help("length to reach in target-length mode")
+                .value_parser(clap::value_parser!(u16).range(4..))
+                .default_value("30"),
+        )
+        .arg(
+            Arg::new("shrink_interval")
+                .long("shrink_interval")
+                .help("seconds between two steps of the border advancing in battle-royale mode")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("daily")
+                .long("daily")
+                .help(
+                    "plays the challenge of the day, a time-attack round on a board generated \
+                     from the date; the first attempt of the day is the official one",
+                )
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(GAMEPLAY_ARGS),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .help(
+                    "continues the game saved by pressing F2, \
+                     with the settings it was started with",
+                )
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(GAMEPLAY_ARGS)
+                .conflicts_with_all([
+                    "daily",
+                    "steering",
+                    "two_key_steering",
+                    "pass_through_teammates",
+                ]),
+        )
+        .arg(
+            Arg::new("wreckage")
+                .long("wreckage")
+                .help("what eliminated snakes leave behind")
+                .value_parser(["vanish", "obstacle", "food"])
+                .default_value("vanish"),
+        )
+        .arg(
+            Arg::new("food")
+                .long("food")
+                .help("number of pieces of food in the arena at the same time")
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("food_spawn")
+                .long("food_spawn")
+                .help("where new food appears")
+                .value_parser(["uniform", "away-from-heads", "near-center", "clustered"])
+                .default_value("uniform"),
+        )
+        .arg(
+            Arg::new("food_lifetime")
+                .long("food_lifetime")
+                .help("seconds after which uneaten food moves somewhere else")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("golden_food")
+                .long("golden_food")
+                .help(
+                    "chance in percent of new food being golden, which is worth 5 points \
+                     and makes snakes grow three times as much; off unless set",
+                )
+                .value_parser(clap::value_parser!(u8).range(0..=100))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("growth")
+                .long("growth")
+                .help("number of segments a snake grows by when eating regular food")
+                .value_parser(clap::value_parser!(u16).range(1..))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("scoring")
+                .long("scoring")
+                .help(
+                    "flat counts the points of the food, speed multiplies them by the speed \
+                     of the game, length by the length of the snake",
+                )
+                .value_parser(["flat", "speed", "length"])
+                .default_value("flat"),
+        )
+        .arg(
+            Arg::new("teams")
+                .long("teams")
+                .help("assigns players to teams, e.g. 1,2,1,2 for two teams of two")
+                .value_parser(clap::value_parser!(u8).range(1..=MAX_PLAYERS as i64))
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("pass_through_teammates")
+                .long("pass_through_teammates")
+                .help("lets snakes pass through the bodies of their teammates")
+                .action(ArgAction::SetTrue)
+                .requires("teams"),
+        )
+        .arg(Arg::new("theme").long("theme").help(format!(
+            "selects a color theme, either one of {} or one defined in the theme file",
+            BUILTIN_THEME_NAMES.join(", ")
+        )))
+        .arg(
+            Arg::new("theme_file")
+                .long("theme_file")
+                .help("loads custom color themes from a file")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("arena")
+                .long("arena")
+                .help("sets the size of the arena as WIDTHxHEIGHT in cells")
+                .value_parser(parse_arena_size)
+                .default_value("40x40"),
+        )
+        .arg(
+            Arg::new("level")
+                .long("level")
+                .help(
+                    "loads walls and portals from a level file, \
+                     which also sets the size of the arena",
+                )
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("arena"),
+        )
+        .arg(
+            Arg::new("portals")
+                .long("portals")
+                .help("number of portals placed at random at the start of every round")
+                .value_parser(clap::value_parser!(u16))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("patrols")
+                .long("patrols")
+                .help(
+                    "number of deadly blocks patrolling back and forth, \
+                     placed at random at the start of every round",
+                )
+                .value_parser(clap::value_parser!(u16))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("mines")
+                .long("mines")
+                .help(
+                    "number of mines in the arena at the same time, \
+                     which blink before they become deadly and vanish after a while",
+                )
+                .value_parser(clap::value_parser!(u16))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("render")
+                .long("render")
+                .help("draws the game using colored blocks, half blocks (two rows per line), lines showing the direction of the snakes or ASCII characters, auto picks what suits the terminal")
+                .value_parser(["auto", "blocks", "half-blocks", "directional", "ascii"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .help("enables colored output, auto respects NO_COLOR and TERM=dumb")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("no_side_panel")
+                .long("no_side_panel")
+                .help("hides the live stats shown next to the arena on wide terminals")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_alternate_screen")
+                .long("no_alternate_screen")
+                .help(
+                    "draws on the normal screen instead of the alternate one, for terminals \
+                     and recorders that don't support it; the last frame stays visible",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("mouse")
+                .long("mouse")
+                .help("player 1 can also steer by clicking or dragging towards a point in the arena")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("keyboard_enhancement")
+                .long("keyboard_enhancement")
+                .help(
+                    "uses the kitty keyboard protocol if the terminal supports it, \
+                     which reports ESC without delay and keys pressed with modifiers unambiguously",
+                )
                 .action(ArgAction::SetTrue),
         )
         .arg(
@@ -41,11 +316,22 @@ fn main() -> Result<()> {
                 .short('t')
                 .long("two_key_steering")
                 .help("steer the snakes using two keys only (increased difficulty)")
-                .action(ArgAction::SetTrue),
+                .action(ArgAction::SetTrue)
+                .conflicts_with("steering"),
         )
-        .get_matches();
+        .arg(
+            Arg::new("steering")
+                .long("steering")
+                .help(
+                    "steering mode of each player, e.g. four-key,two-key; \
+                     a single mode applies to all players",
+                )
+                .value_parser(["four-key", "two-key", "hybrid"])
+                .value_delimiter(','),
+        );
+    let matches = cmd.get_matches_mut();
 
-    let mut target_fps = 8.0;
+    let mut target_fps = NORMAL_SPEED;
     if *matches.get_one::<bool>("hard").unwrap_or(&false) {
         target_fps *= 1.5;
     } else if *matches.get_one::<bool>("easy").unwrap_or(&false) {
@@ -58,11 +344,270 @@ fn main() -> Result<()> {
     if *matches.get_one::<bool>("multiplayer").unwrap_or(&false) {
         num_players += 1;
     }
+    if let Some(players) = matches.get_one::<u8>("players") {
+        num_players = *players as usize;
+    }
     let num_players = num_players;
 
-    let is_four_key_steering = !matches
-        .get_one::<bool>("two_key_steering")
+    let daily = if *matches.get_one::<bool>("daily").unwrap_or(&false) {
+        Some(DailyChallenge::today())
+    } else {
+        None
+    };
+
+    let game_mode = match matches.get_one::<String>("mode").map(String::as_str) {
+        _ if daily.is_some() => GameMode::TimeAttack(DAILY_TIME_LIMIT),
+        Some("elimination") => GameMode::LastSnakeStanding,
+        Some("timed") => GameMode::Timed(Duration::from_secs(
+            *matches.get_one::<u64>("time_limit").unwrap(),
+        )),
+        Some("battle-royale") => GameMode::BattleRoyale(Duration::from_secs(
+            *matches.get_one::<u64>("shrink_interval").unwrap(),
+        )),
+        Some("time-attack") => GameMode::TimeAttack(Duration::from_secs(
+            *matches.get_one::<u64>("time_limit").unwrap(),
+        )),
+        Some("target-length") => {
+            GameMode::TargetLength(*matches.get_one::<u16>("target_length").unwrap() as usize)
+        }
+        _ => GameMode::Classic,
+    };
+    if game_mode.score_table().is_some() && num_players != 1 {
+        cmd.error(
+            ErrorKind::ArgumentConflict,
+            "the time-attack and target-length modes are for a single player",
+        )
+        .exit();
+    }
+
+    let teams = matches
+        .get_many::<u8>("teams")
+        .map(|teams| teams.map(|team| *team as usize - 1).collect::<Vec<usize>>());
+    if let Some(teams) = &teams {
+        if teams.len() != num_players {
+            cmd.error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "--teams assigns {} players, but {} are playing",
+                    teams.len(),
+                    num_players
+                ),
+            )
+            .exit();
+        }
+    }
+
+    let pass_through_teammates = *matches
+        .get_one::<bool>("pass_through_teammates")
         .unwrap_or(&false);
 
-    SnakeGame::new(num_players, target_fps, is_four_key_steering).run()
+    let custom_themes = match matches.get_one::<PathBuf>("theme_file") {
+        Some(path) => load_themes(path).unwrap_or_else(|err| {
+            cmd.error(ErrorKind::Io, format!("could not load themes: {}", err))
+                .exit()
+        }),
+        None => vec![],
+    };
+    let theme = match matches.get_one::<String>("theme") {
+        Some(name) => custom_themes
+            .iter()
+            .find(|theme| theme.name == *name)
+            .cloned()
+            .or_else(|| Theme::builtin(name))
+            .unwrap_or_else(|| {
+                cmd.error(ErrorKind::InvalidValue, format!("unknown theme '{}'", name))
+                    .exit()
+            }),
+        // a theme file without explicit choice selects its first theme
+        None => custom_themes.into_iter().next().unwrap_or_default(),
+    };
+
+    let food_spawn = match matches.get_one::<String>("food_spawn").map(String::as_str) {
+        Some("away-from-heads") => SpawnPolicy::AwayFromHeads,
+        Some("near-center") => SpawnPolicy::NearCenter,
+        Some("clustered") => SpawnPolicy::Clustered,
+        _ => SpawnPolicy::Uniform,
+    };
+
+    let scoring = match matches.get_one::<String>("scoring").map(String::as_str) {
+        Some("speed") => Scoring::Speed,
+        Some("length") => Scoring::Length,
+        _ => Scoring::Flat,
+    };
+
+    let is_color_enabled = match matches.get_one::<String>("color").map(String::as_str) {
+        Some("always") => true,
+        Some("never") => false,
+        _ => is_color_supported(),
+    };
+    force_color_output(is_color_enabled);
+
+    let render_mode = match matches.get_one::<String>("render").map(String::as_str) {
+        Some("blocks") => RenderMode::Blocks,
+        Some("half-blocks") => RenderMode::HalfBlocks,
+        Some("directional") => RenderMode::Directional,
+        Some("ascii") => RenderMode::Ascii,
+        _ => RenderMode::detect(is_color_enabled),
+    };
+
+    let wreckage = match matches.get_one::<String>("wreckage").map(String::as_str) {
+        Some("obstacle") => Wreckage::Obstacle,
+        Some("food") => Wreckage::Food,
+        _ => Wreckage::Vanish,
+    };
+
+    let mut steering = match matches.get_many::<String>("steering") {
+        Some(modes) => modes
+            .map(|mode| match mode.as_str() {
+                "two-key" => SteeringMode::TwoKeys,
+                "hybrid" => SteeringMode::Hybrid,
+                _ => SteeringMode::FourKeys,
+            })
+            .collect::<Vec<SteeringMode>>(),
+        None if *matches
+            .get_one::<bool>("two_key_steering")
+            .unwrap_or(&false) =>
+        {
+            vec![SteeringMode::TwoKeys]
+        }
+        None => vec![SteeringMode::FourKeys],
+    };
+    if steering.len() == 1 {
+        steering = vec![steering[0]; num_players];
+    } else if steering.len() != num_players {
+        cmd.error(
+            ErrorKind::ValueValidation,
+            format!(
+                "--steering sets the mode of {} players, but {} are playing",
+                steering.len(),
+                num_players
+            ),
+        )
+        .exit();
+    }
+
+    let level = matches.get_one::<PathBuf>("level").map(|path| {
+        load_level(path).unwrap_or_else(|err| {
+            cmd.error(ErrorKind::Io, format!("could not load level: {}", err))
+                .exit()
+        })
+    });
+    let (arena_width, arena_height) = match &level {
+        Some(level) => (level.width, level.height),
+        None => *matches.get_one::<(usize, usize)>("arena").unwrap(),
+    };
+    let level = match &daily {
+        Some(daily) => Some(daily.level(
+            arena_width,
+            arena_height,
+            &Snake::new(0, arena_width, arena_height).body_pos,
+        )),
+        None => level,
+    };
+    if let Some(level) = &level {
+        if arena_width < MIN_ARENA_WIDTH || arena_height < MIN_ARENA_HEIGHT {
+            cmd.error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "the level must be at least {}x{} including the border, but is {}x{}",
+                    MIN_ARENA_WIDTH, MIN_ARENA_HEIGHT, arena_width, arena_height
+                ),
+            )
+            .exit();
+        }
+        if arena_width > MAX_ARENA_WIDTH || arena_height > MAX_ARENA_HEIGHT {
+            cmd.error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "the level must be at most {}x{} including the border, but is {}x{}",
+                    MAX_ARENA_WIDTH, MAX_ARENA_HEIGHT, arena_width, arena_height
+                ),
+            )
+            .exit();
+        }
+        for player_idx in 0..num_players {
+            let snake = Snake::new(player_idx, arena_width, arena_height);
+            if snake.body_pos.iter().any(|pos| level.is_blocked(pos)) {
+                cmd.error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "the level blocks the start position of player {}",
+                        player_idx + 1
+                    ),
+                )
+                .exit();
+            }
+        }
+    }
+
+    let settings = GameSettings {
+        num_players,
+        arena_width,
+        arena_height,
+        target_fps,
+        steering,
+        game_mode,
+        wreckage,
+        food_count: *matches.get_one::<u16>("food").unwrap() as usize,
+        food_spawn,
+        food_lifetime: matches
+            .get_one::<u64>("food_lifetime")
+            .map(|secs| Duration::from_secs(*secs)),
+        golden_food_chance: match daily {
+            Some(_) => DAILY_GOLDEN_FOOD_CHANCE,
+            None => *matches.get_one::<u8>("golden_food").unwrap() as f64 / 100.0,
+        },
+        base_growth: *matches.get_one::<u16>("growth").unwrap() as usize,
+        scoring,
+        level,
+        random_portals: *matches.get_one::<u16>("portals").unwrap() as usize,
+        random_patrols: *matches.get_one::<u16>("patrols").unwrap() as usize,
+        mine_count: match daily {
+            Some(_) => DAILY_MINES,
+            None => *matches.get_one::<u16>("mines").unwrap() as usize,
+        },
+        daily,
+        teams,
+        pass_through_teammates,
+        theme: theme.adapted_to_terminal(),
+        render_mode,
+        show_side_panel: !*matches.get_one::<bool>("no_side_panel").unwrap_or(&false),
+        use_alternate_screen: !*matches
+            .get_one::<bool>("no_alternate_screen")
+            .unwrap_or(&false),
+        is_mouse_steering: *matches.get_one::<bool>("mouse").unwrap_or(&false),
+        use_keyboard_enhancement: *matches
+            .get_one::<bool>("keyboard_enhancement")
+            .unwrap_or(&false),
+    };
+    if *matches.get_one::<bool>("resume").unwrap_or(&false) {
+        let (settings, saved_round) = load_game(settings).unwrap_or_else(|err| {
+            cmd.error(ErrorKind::Io, format!("could not resume the game: {}", err))
+                .exit()
+        });
+        SnakeGame::resume(settings, saved_round).run()
+    } else {
+        SnakeGame::new(settings).run()
+    }
+}
+
+fn parse_arena_size(value: &str) -> std::result::Result<(usize, usize), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| "expected WIDTHxHEIGHT, e.g. 80x60".to_string())?;
+    let width: usize = width.trim().parse().map_err(|e| format!("{}", e))?;
+    let height: usize = height.trim().parse().map_err(|e| format!("{}", e))?;
+    if width < MIN_ARENA_WIDTH || height < MIN_ARENA_HEIGHT {
+        return Err(format!(
+            "the arena must be at least {}x{}",
+            MIN_ARENA_WIDTH, MIN_ARENA_HEIGHT
+        ));
+    }
+    if width > MAX_ARENA_WIDTH || height > MAX_ARENA_HEIGHT {
+        return Err(format!(
+            "the arena must be at most {}x{}",
+            MAX_ARENA_WIDTH, MAX_ARENA_HEIGHT
+        ));
+    }
+    Ok((width, height))
 }
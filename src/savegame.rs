@@ -0,0 +1,955 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::arena::{Arena, Food, FoodKind, SpawnPolicy};
+use crate::hazard::Hazard;
+use crate::level::Level;
+use crate::scores::data_dir;
+use crate::screen_buffer::Coordinate;
+use crate::snake::{
+    Direction, GameMode, GameSettings, Player, Scoring, Snake, SteeringMode, Wreckage,
+    MAX_ARENA_HEIGHT, MAX_ARENA_WIDTH, MAX_PLAYERS, MIN_ARENA_HEIGHT, MIN_ARENA_WIDTH,
+    NORMAL_SPEED,
+};
+
+const SAVE_FILE_NAME: &str = "savegame.txt";
+
+/// A round in progress, as it was when a player saved and quit.
+pub struct SavedRound {
+    pub players: Vec<Player>,
+    pub arena: Arena,
+    pub tick: u64,
+    /// time played in the round, without pauses
+    pub elapsed: Duration,
+    /// best score of any team in the session
+    pub high_score: usize,
+    /// state of the random number generators, so that the same food appears after
+    /// resuming as would have without saving
+    pub rng: ChaCha12Rng,
+    pub food_kind_rng: ChaCha12Rng,
+}
+
+fn save_file_path() -> Result<PathBuf> {
+    data_dir()
+        .map(|dir| dir.join(SAVE_FILE_NAME))
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                "there is no place to keep the saved game, neither HOME nor APPDATA is set",
+            )
+        })
+}
+
+/// Writes the game to the save file and returns its path.
+pub fn save_game(settings: &GameSettings, round: &SavedRound) -> Result<PathBuf> {
+    let path = save_file_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, format_saved_game(settings, round))?;
+    Ok(path)
+}
+
+/// Formats the settings that shape the game and the state of the round, one
+/// `key = value` per line. Settings that only change how the game looks are left out,
+/// they are taken from the command line when resuming.
+fn format_saved_game(settings: &GameSettings, round: &SavedRound) -> String {
+    let mut lines = vec![
+        "# saved game of rs_snake, continue it with --resume".to_string(),
+        format!("players = {}", settings.num_players),
+        format!("arena = {}x{}", settings.arena_width, settings.arena_height),
+        format!("speed = {}", settings.target_fps),
+        format!(
+            "steering = {}",
+            settings
+                .steering
+                .iter()
+                .map(|&steering| steering_name(steering))
+                .collect::<Vec<&str>>()
+                .join(",")
+        ),
+        format!("mode = {}", mode_name(settings.game_mode)),
+        format!("wreckage = {}", wreckage_name(settings.wreckage)),
+        format!("food = {}", settings.food_count),
+        format!("food_spawn = {}", spawn_policy_name(settings.food_spawn)),
+        format!("golden_food = {}", settings.golden_food_chance),
+        format!("growth = {}", settings.base_growth),
+        format!("scoring = {}", scoring_name(settings.scoring)),
+        format!("random_portals = {}", settings.random_portals),
+        format!("random_patrols = {}", settings.random_patrols),
+        format!("mines = {}", settings.mine_count),
+        format!(
+            "pass_through_teammates = {}",
+            settings.pass_through_teammates
+        ),
+    ];
+    if let Some(lifetime) = settings.food_lifetime {
+        lines.push(format!("food_lifetime = {}", lifetime.as_secs()));
+    }
+    if let Some(teams) = &settings.teams {
+        let teams: Vec<String> = teams.iter().map(|team| (team + 1).to_string()).collect();
+        lines.push(format!("teams = {}", teams.join(",")));
+    }
+    // the level is needed again for the rounds after the resumed one
+    if let Some(level) = &settings.level {
+        lines.push(format!("level = {}x{}", level.width, level.height));
+        lines.push(format!("level_walls = {}", format_cells(&level.walls)));
+        lines.push(format!("level_gates = {}", format_cells(&level.gates)));
+        for (first, second) in &level.portals {
+            lines.push(format!(
+                "level_portal = {}",
+                format_cells(&[*first, *second])
+            ));
+        }
+        for (pos, direction) in &level.patrols {
+            lines.push(format!(
+                "level_patrol = {} {}",
+                format_cell(pos),
+                direction_name(*direction)
+            ));
+        }
+    }
+
+    lines.push(format!("tick = {}", round.tick));
+    lines.push(format!("elapsed_ms = {}", round.elapsed.as_millis()));
+    lines.push(format!("high_score = {}", round.high_score));
+    lines.push(format!("rng = {}", format_rng(&round.rng)));
+    lines.push(format!(
+        "food_kind_rng = {}",
+        format_rng(&round.food_kind_rng)
+    ));
+    for player in &round.players {
+        lines.push(format!(
+            "snake = {} {} {} {} {}",
+            player.team_idx + 1,
+            player.score,
+            player.has_crashed,
+            direction_name(player.snake.direction),
+            format_cells(&player.snake.body_pos)
+        ));
+    }
+    let arena = &round.arena;
+    for food in &arena.food {
+        let mut line = format!(
+            "food_at = {} {}",
+            format_cell(&food.pos),
+            food_kind_name(food.kind)
+        );
+        if let Some(expires_at_tick) = food.expires_at_tick {
+            line.push_str(&format!(" {}", expires_at_tick));
+        }
+        lines.push(line);
+    }
+    lines.push(format!("obstacles = {}", format_cells(&arena.obstacles)));
+    lines.push(format!("wreck_food = {}", format_cells(&arena.wreck_food)));
+    for (first, second) in &arena.portals {
+        lines.push(format!("portal = {}", format_cells(&[*first, *second])));
+    }
+    for hazard in &arena.hazards {
+        lines.push(match hazard {
+            Hazard::Patrol { pos, direction } => format!(
+                "patrol = {} {}",
+                format_cell(pos),
+                direction_name(*direction)
+            ),
+            Hazard::Gate {
+                cells,
+                is_closed,
+                next_switch_tick,
+            } => format!(
+                "gate = {} {} {}",
+                is_closed,
+                next_switch_tick,
+                format_cells(cells)
+            ),
+            Hazard::Mine {
+                pos,
+                armed_at_tick,
+                vanishes_at_tick,
+            } => format!(
+                "mine = {} {} {}",
+                format_cell(pos),
+                armed_at_tick,
+                vanishes_at_tick
+            ),
+        });
+    }
+    lines.push(format!("inset = {}", arena.inset));
+    if let Some(next_shrink_tick) = arena.next_shrink_tick {
+        lines.push(format!("next_shrink_tick = {}", next_shrink_tick));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Loads the saved game. The settings that shape the game replace those in `settings`.
+/// The save file is kept until [`remove_saved_game`] is called.
+pub fn load_game(settings: GameSettings) -> Result<(GameSettings, SavedRound)> {
+    let path = save_file_path()?;
+    if !path.exists() {
+        return Err(Error::new(ErrorKind::NotFound, "there is no saved game"));
+    }
+    let content = fs::read_to_string(&path)?;
+    parse_saved_game(&content, &path.display().to_string(), settings)
+}
+
+/// Parses a saved game, `source` names where it comes from in error messages.
+fn parse_saved_game(
+    content: &str,
+    source: &str,
+    settings: GameSettings,
+) -> Result<(GameSettings, SavedRound)> {
+    let mut settings = settings;
+    settings.level = None;
+    settings.food_lifetime = None;
+    settings.teams = None;
+    let mut level: Option<Level> = None;
+    let mut round = SavedRound {
+        players: vec![],
+        arena: Arena::new(settings.arena_width, settings.arena_height),
+        tick: 0,
+        elapsed: Duration::ZERO,
+        high_score: 0,
+        rng: ChaCha12Rng::from_seed([0; 32]),
+        food_kind_rng: ChaCha12Rng::from_seed([0; 32]),
+    };
+    let mut has_rng = (false, false);
+
+    for (line_idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let invalid = |msg: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}:{}: {}", source, line_idx + 1, msg),
+            )
+        };
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("expected key = value, got '{}'", line)))?;
+        let (key, value) = (key.trim(), value.trim());
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        let parsed = match key {
+            "players" => parse(value).map(|num_players| settings.num_players = num_players),
+            "arena" => parse_size(value).map(|(width, height)| {
+                settings.arena_width = width;
+                settings.arena_height = height;
+                round.arena.width = width;
+                round.arena.height = height;
+            }),
+            // between --easy and --hard
+            "speed" => parse(value)
+                .filter(|speed| (NORMAL_SPEED * 0.7..=NORMAL_SPEED * 1.5).contains(speed))
+                .map(|speed| settings.target_fps = speed),
+            "steering" => value
+                .split(',')
+                .map(parse_steering)
+                .collect::<Option<Vec<SteeringMode>>>()
+                .map(|steering| settings.steering = steering),
+            "mode" => parse_mode(&fields).map(|mode| settings.game_mode = mode),
+            "wreckage" => parse_wreckage(value).map(|wreckage| settings.wreckage = wreckage),
+            "food" => parse_count(value, 1).map(|food_count| settings.food_count = food_count),
+            "food_spawn" => parse_spawn_policy(value).map(|policy| settings.food_spawn = policy),
+            "food_lifetime" => parse(value)
+                .filter(|&secs| secs >= 1)
+                .map(|secs| settings.food_lifetime = Some(Duration::from_secs(secs))),
+            "golden_food" => parse(value)
+                .filter(|chance| (0.0..=1.0).contains(chance))
+                .map(|chance| settings.golden_food_chance = chance),
+            "growth" => parse_count(value, 1).map(|growth| settings.base_growth = growth),
+            "scoring" => parse_scoring(value).map(|scoring| settings.scoring = scoring),
+            "random_portals" => parse_count(value, 0).map(|count| settings.random_portals = count),
+            "random_patrols" => parse_count(value, 0).map(|count| settings.random_patrols = count),
+            "mines" => parse_count(value, 0).map(|count| settings.mine_count = count),
+            "pass_through_teammates" => {
+                parse(value).map(|pass_through| settings.pass_through_teammates = pass_through)
+            }
+            "teams" => value
+                .split(',')
+                .map(|team| {
+                    parse::<usize>(team)
+                        .filter(|team| (1..=MAX_PLAYERS).contains(team))
+                        .map(|team| team - 1)
+                })
+                .collect::<Option<Vec<usize>>>()
+                .map(|teams| settings.teams = Some(teams)),
+            "level" => parse_size(value).map(|(width, height)| {
+                level = Some(Level {
+                    width,
+                    height,
+                    walls: vec![],
+                    portals: vec![],
+                    gates: vec![],
+                    patrols: vec![],
+                })
+            }),
+            "level_walls" | "level_gates" | "level_portal" | "level_patrol" => {
+                let level = level
+                    .as_mut()
+                    .ok_or_else(|| invalid(format!("'{}' before 'level'", key)))?;
+                match key {
+                    "level_walls" => parse_cells(&fields).map(|walls| level.walls = walls),
+                    "level_gates" => parse_cells(&fields).map(|gates| level.gates = gates),
+                    "level_portal" => {
+                        parse_portal(&fields).map(|portal| level.portals.push(portal))
+                    }
+                    _ => parse_patrol(&fields)
+                        .map(|(pos, direction)| level.patrols.push((pos, direction))),
+                }
+            }
+            "tick" => parse(value).map(|tick| round.tick = tick),
+            "elapsed_ms" => parse(value).map(|ms| round.elapsed = Duration::from_millis(ms)),
+            "high_score" => parse(value).map(|high_score| round.high_score = high_score),
+            "rng" => parse_rng(&fields).map(|rng| {
+                round.rng = rng;
+                has_rng.0 = true;
+            }),
+            "food_kind_rng" => parse_rng(&fields).map(|rng| {
+                round.food_kind_rng = rng;
+                has_rng.1 = true;
+            }),
+            "snake" => parse_player(round.players.len(), &fields, &settings)
+                .map(|player| round.players.push(player)),
+            "food_at" => parse_food(&fields).map(|food| round.arena.food.push(food)),
+            "obstacles" => parse_cells(&fields).map(|cells| round.arena.obstacles = cells),
+            "wreck_food" => parse_cells(&fields).map(|cells| round.arena.wreck_food = cells),
+            "portal" => parse_portal(&fields).map(|portal| round.arena.portals.push(portal)),
+            "patrol" => parse_patrol(&fields).map(|(pos, direction)| {
+                round.arena.hazards.push(Hazard::Patrol { pos, direction })
+            }),
+            "gate" => parse_gate(&fields).map(|gate| round.arena.hazards.push(gate)),
+            "mine" => parse_mine(&fields).map(|mine| round.arena.hazards.push(mine)),
+            "inset" => parse(value).map(|inset| round.arena.inset = inset),
+            "next_shrink_tick" => {
+                parse(value).map(|tick| round.arena.next_shrink_tick = Some(tick))
+            }
+            _ => return Err(invalid(format!("unknown key '{}'", key))),
+        };
+        parsed.ok_or_else(|| invalid(format!("invalid value '{}' of '{}'", value, key)))?;
+    }
+    settings.level = level;
+
+    let invalid = |msg: String| Error::new(ErrorKind::InvalidData, format!("{}: {}", source, msg));
+    if has_rng != (true, true) {
+        return Err(invalid(
+            "the state of the random number generators is missing".to_string(),
+        ));
+    }
+    let num_players = settings.num_players;
+    if num_players == 0 || num_players > MAX_PLAYERS {
+        return Err(invalid(format!(
+            "invalid number of players {}",
+            num_players
+        )));
+    }
+    if round.players.len() != num_players
+        || settings.steering.len() != num_players
+        || settings
+            .teams
+            .as_ref()
+            .is_some_and(|teams| teams.len() != num_players)
+    {
+        return Err(invalid(format!(
+            "expected snakes, steering and teams of {} players",
+            num_players
+        )));
+    }
+    if settings.game_mode.score_table().is_some() && num_players != 1 {
+        return Err(invalid(
+            "the time-attack and target-length modes are for a single player".to_string(),
+        ));
+    }
+    if round.players.iter().any(|player| {
+        player.snake.body_pos.is_empty()
+            || player.team_idx != settings.team_of_player(player.player_idx)
+    }) {
+        return Err(invalid("invalid snake".to_string()));
+    }
+    if settings.level.as_ref().is_some_and(|level| {
+        level.width != settings.arena_width || level.height != settings.arena_height
+    }) {
+        return Err(invalid(
+            "the level doesn't match the size of the arena".to_string(),
+        ));
+    }
+    let is_outside =
+        |pos: &Coordinate| pos.row >= settings.arena_height || pos.col >= settings.arena_width;
+    let arena = &round.arena;
+    let level_cells = settings.level.iter().flat_map(|level| {
+        level
+            .walls
+            .iter()
+            .chain(&level.gates)
+            .chain(
+                level
+                    .portals
+                    .iter()
+                    .flat_map(|(first, second)| [first, second]),
+            )
+            .chain(level.patrols.iter().map(|(pos, _)| pos))
+    });
+    if round
+        .players
+        .iter()
+        .flat_map(|player| player.snake.body_pos.iter())
+        .chain(arena.food.iter().map(|food| &food.pos))
+        .chain(&arena.obstacles)
+        .chain(&arena.wreck_food)
+        .chain(
+            arena
+                .portals
+                .iter()
+                .flat_map(|(first, second)| [first, second]),
+        )
+        .chain(level_cells)
+        .any(is_outside)
+        || arena
+            .hazards
+            .iter()
+            .flat_map(|hazard| hazard.cells())
+            .any(|pos| is_outside(&pos))
+    {
+        return Err(invalid("found a cell outside of the arena".to_string()));
+    }
+    // the border never grows over the last snakes standing
+    if 2 * arena.inset + 3 > settings.arena_width.min(settings.arena_height)
+        || round
+            .players
+            .iter()
+            .filter(|player| !player.has_crashed)
+            .flat_map(|player| player.snake.body_pos.iter())
+            .any(|pos| !arena.is_inside(pos))
+    {
+        return Err(invalid(
+            "found a snake that should have crashed into the border".to_string(),
+        ));
+    }
+    // the next round starts at the usual positions
+    if let Some(level) = &settings.level {
+        if (0..num_players).any(|player_idx| {
+            Snake::new(player_idx, settings.arena_width, settings.arena_height)
+                .body_pos
+                .iter()
+                .any(|pos| level.is_blocked(pos))
+        }) {
+            return Err(invalid(
+                "the level blocks the start position of a player".to_string(),
+            ));
+        }
+    }
+
+    Ok((settings, round))
+}
+
+/// Removes the save file once the resumed round is under way, so that a game is resumed
+/// only once.
+pub fn remove_saved_game() -> Result<()> {
+    fs::remove_file(save_file_path()?)
+}
+
+fn parse<T: FromStr>(value: &str) -> Option<T> {
+    value.parse().ok()
+}
+
+/// Parses a number that is at least `min` and fits the range allowed on the command line.
+fn parse_count(value: &str, min: usize) -> Option<usize> {
+    parse::<u16>(value)
+        .map(usize::from)
+        .filter(|&count| count >= min)
+}
+
+/// Parses the size of the arena, which stays within the limits of the command line.
+fn parse_size(value: &str) -> Option<(usize, usize)> {
+    let (width, height) = value.split_once('x')?;
+    Some((parse(width)?, parse(height)?)).filter(|&(width, height)| {
+        (MIN_ARENA_WIDTH..=MAX_ARENA_WIDTH).contains(&width)
+            && (MIN_ARENA_HEIGHT..=MAX_ARENA_HEIGHT).contains(&height)
+    })
+}
+
+/// Formats the state of a random number generator as its seed in hex, the stream and
+/// the position within the stream.
+fn format_rng(rng: &ChaCha12Rng) -> String {
+    let seed: String = rng
+        .get_seed()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{} {} {}", seed, rng.get_stream(), rng.get_word_pos())
+}
+
+fn parse_rng(fields: &[&str]) -> Option<ChaCha12Rng> {
+    match fields {
+        [seed, stream, word_pos] => {
+            let mut rng = ChaCha12Rng::from_seed(parse_seed(seed)?);
+            rng.set_stream(parse(stream)?);
+            rng.set_word_pos(parse(word_pos)?);
+            Some(rng)
+        }
+        _ => None,
+    }
+}
+
+fn parse_seed(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut seed = [0; 32];
+    for (idx, byte) in seed.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(2 * idx..2 * idx + 2)?, 16).ok()?;
+    }
+    Some(seed)
+}
+
+fn format_cell(pos: &Coordinate) -> String {
+    format!("{},{}", pos.row, pos.col)
+}
+
+fn format_cells(cells: &[Coordinate]) -> String {
+    cells
+        .iter()
+        .map(format_cell)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn parse_cell(value: &str) -> Option<Coordinate> {
+    let (row, col) = value.split_once(',')?;
+    Some(Coordinate {
+        row: parse(row)?,
+        col: parse(col)?,
+    })
+}
+
+fn parse_cells(fields: &[&str]) -> Option<Vec<Coordinate>> {
+    fields.iter().map(|field| parse_cell(field)).collect()
+}
+
+fn parse_portal(fields: &[&str]) -> Option<(Coordinate, Coordinate)> {
+    match fields {
+        [first, second] => Some((parse_cell(first)?, parse_cell(second)?)),
+        _ => None,
+    }
+}
+
+fn parse_patrol(fields: &[&str]) -> Option<(Coordinate, Direction)> {
+    match fields {
+        [pos, direction] => Some((parse_cell(pos)?, parse_direction(direction)?)),
+        _ => None,
+    }
+}
+
+fn parse_gate(fields: &[&str]) -> Option<Hazard> {
+    match fields {
+        [is_closed, next_switch_tick, cells @ ..] => Some(Hazard::Gate {
+            cells: parse_cells(cells)?,
+            is_closed: parse(is_closed)?,
+            next_switch_tick: parse(next_switch_tick)?,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_mine(fields: &[&str]) -> Option<Hazard> {
+    match fields {
+        [pos, armed_at_tick, vanishes_at_tick] => Some(Hazard::Mine {
+            pos: parse_cell(pos)?,
+            armed_at_tick: parse(armed_at_tick)?,
+            vanishes_at_tick: parse(vanishes_at_tick)?,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_food(fields: &[&str]) -> Option<Food> {
+    let (pos, kind, expires_at_tick) = match fields {
+        [pos, kind] => (pos, kind, None),
+        [pos, kind, expires_at_tick] => (pos, kind, Some(parse(expires_at_tick)?)),
+        _ => return None,
+    };
+    Some(Food {
+        pos: parse_cell(pos)?,
+        kind: match *kind {
+            "regular" => FoodKind::Regular,
+            "golden" => FoodKind::Golden,
+            _ => return None,
+        },
+        expires_at_tick,
+    })
+}
+
+/// Parses `team score has_crashed direction body...` of the player with the given index.
+fn parse_player(player_idx: usize, fields: &[&str], settings: &GameSettings) -> Option<Player> {
+    match fields {
+        [team, score, has_crashed, direction, body @ ..] => {
+            let team_idx = parse::<usize>(team)?.checked_sub(1)?;
+            let steering = *settings.steering.get(player_idx)?;
+            let snake = Snake {
+                body_pos: parse_cells(body)?,
+                direction: parse_direction(direction)?,
+            };
+            let mut player = Player::new(player_idx, team_idx, steering, snake);
+            player.score = parse(score)?;
+            player.has_crashed = parse(has_crashed)?;
+            Some(player)
+        }
+        _ => None,
+    }
+}
+
+fn food_kind_name(kind: FoodKind) -> &'static str {
+    match kind {
+        FoodKind::Regular => "regular",
+        FoodKind::Golden => "golden",
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+fn parse_direction(value: &str) -> Option<Direction> {
+    match value {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+fn steering_name(steering: SteeringMode) -> &'static str {
+    match steering {
+        SteeringMode::FourKeys => "four-key",
+        SteeringMode::TwoKeys => "two-key",
+        SteeringMode::Hybrid => "hybrid",
+    }
+}
+
+fn parse_steering(value: &str) -> Option<SteeringMode> {
+    match value {
+        "four-key" => Some(SteeringMode::FourKeys),
+        "two-key" => Some(SteeringMode::TwoKeys),
+        "hybrid" => Some(SteeringMode::Hybrid),
+        _ => None,
+    }
+}
+
+/// Names the mode like `--mode`, followed by its time limit, shrink interval or target.
+fn mode_name(mode: GameMode) -> String {
+    match mode {
+        GameMode::Classic => "classic".to_string(),
+        GameMode::LastSnakeStanding => "elimination".to_string(),
+        GameMode::Timed(time_limit) => format!("timed {}", time_limit.as_secs()),
+        GameMode::BattleRoyale(interval) => format!("battle-royale {}", interval.as_secs()),
+        GameMode::TimeAttack(time_limit) => format!("time-attack {}", time_limit.as_secs()),
+        GameMode::TargetLength(target_length) => format!("target-length {}", target_length),
+    }
+}
+
+fn parse_secs(value: &str) -> Option<Duration> {
+    parse(value)
+        .filter(|&secs| secs >= 1)
+        .map(Duration::from_secs)
+}
+
+fn parse_mode(fields: &[&str]) -> Option<GameMode> {
+    match fields {
+        ["classic"] => Some(GameMode::Classic),
+        ["elimination"] => Some(GameMode::LastSnakeStanding),
+        ["timed", secs] => Some(GameMode::Timed(parse_secs(secs)?)),
+        ["battle-royale", secs] => Some(GameMode::BattleRoyale(parse_secs(secs)?)),
+        ["time-attack", secs] => Some(GameMode::TimeAttack(parse_secs(secs)?)),
+        ["target-length", target_length] => {
+            Some(GameMode::TargetLength(parse_count(target_length, 4)?))
+        }
+        _ => None,
+    }
+}
+
+fn wreckage_name(wreckage: Wreckage) -> &'static str {
+    match wreckage {
+        Wreckage::Vanish => "vanish",
+        Wreckage::Obstacle => "obstacle",
+        Wreckage::Food => "food",
+    }
+}
+
+fn parse_wreckage(value: &str) -> Option<Wreckage> {
+    match value {
+        "vanish" => Some(Wreckage::Vanish),
+        "obstacle" => Some(Wreckage::Obstacle),
+        "food" => Some(Wreckage::Food),
+        _ => None,
+    }
+}
+
+fn spawn_policy_name(policy: SpawnPolicy) -> &'static str {
+    match policy {
+        SpawnPolicy::Uniform => "uniform",
+        SpawnPolicy::AwayFromHeads => "away-from-heads",
+        SpawnPolicy::NearCenter => "near-center",
+        SpawnPolicy::Clustered => "clustered",
+    }
+}
+
+fn parse_spawn_policy(value: &str) -> Option<SpawnPolicy> {
+    match value {
+        "uniform" => Some(SpawnPolicy::Uniform),
+        "away-from-heads" => Some(SpawnPolicy::AwayFromHeads),
+        "near-center" => Some(SpawnPolicy::NearCenter),
+        "clustered" => Some(SpawnPolicy::Clustered),
+        _ => None,
+    }
+}
+
+fn scoring_name(scoring: Scoring) -> &'static str {
+    match scoring {
+        Scoring::Flat => "flat",
+        Scoring::Speed => "speed",
+        Scoring::Length => "length",
+    }
+}
+
+fn parse_scoring(value: &str) -> Option<Scoring> {
+    match value {
+        "flat" => Some(Scoring::Flat),
+        "speed" => Some(Scoring::Speed),
+        "length" => Some(Scoring::Length),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::SpawnPolicy;
+    use crate::screen_buffer::RenderMode;
+    use crate::theme::Theme;
+    use rand::Rng;
+
+    fn cell(row: usize, col: usize) -> Coordinate {
+        Coordinate { row, col }
+    }
+
+    /// Settings as they come from the command line when resuming.
+    fn command_line_settings() -> GameSettings {
+        GameSettings {
+            num_players: 1,
+            arena_width: 40,
+            arena_height: 40,
+            target_fps: NORMAL_SPEED,
+            steering: vec![SteeringMode::FourKeys],
+            game_mode: GameMode::Classic,
+            wreckage: Wreckage::Vanish,
+            food_count: 1,
+            food_spawn: SpawnPolicy::Uniform,
+            food_lifetime: None,
+            golden_food_chance: 0.0,
+            base_growth: 3,
+            scoring: Scoring::Flat,
+            level: None,
+            random_portals: 0,
+            random_patrols: 0,
+            mine_count: 0,
+            daily: None,
+            teams: None,
+            pass_through_teammates: false,
+            theme: Theme::default(),
+            render_mode: RenderMode::Ascii,
+            show_side_panel: false,
+            use_alternate_screen: true,
+            is_mouse_steering: false,
+            use_keyboard_enhancement: false,
+        }
+    }
+
+    fn saved_game() -> (GameSettings, SavedRound) {
+        let level = Level {
+            width: 50,
+            height: 30,
+            walls: vec![cell(5, 30), cell(5, 31)],
+            portals: vec![(cell(20, 30), cell(25, 40))],
+            gates: vec![cell(3, 3), cell(3, 4)],
+            patrols: vec![(cell(8, 20), Direction::Right)],
+        };
+        let settings = GameSettings {
+            num_players: 2,
+            arena_width: 50,
+            arena_height: 30,
+            target_fps: NORMAL_SPEED * 1.5,
+            steering: vec![SteeringMode::Hybrid, SteeringMode::TwoKeys],
+            game_mode: GameMode::BattleRoyale(Duration::from_secs(30)),
+            wreckage: Wreckage::Food,
+            food_count: 2,
+            food_spawn: SpawnPolicy::Clustered,
+            food_lifetime: Some(Duration::from_secs(20)),
+            golden_food_chance: 0.25,
+            base_growth: 2,
+            scoring: Scoring::Speed,
+            level: Some(level.clone()),
+            random_portals: 1,
+            random_patrols: 1,
+            mine_count: 1,
+            teams: Some(vec![1, 0]),
+            pass_through_teammates: true,
+            ..command_line_settings()
+        };
+
+        let mut players: Vec<Player> = (0..2)
+            .map(|player_idx| {
+                Player::new(
+                    player_idx,
+                    settings.team_of_player(player_idx),
+                    settings.steering[player_idx],
+                    Snake::new(player_idx, 50, 30),
+                )
+            })
+            .collect();
+        players[0].score = 7;
+        players[1].has_crashed = true;
+        players[1].snake.direction = Direction::Left;
+
+        let mut arena = Arena::from_level(&level);
+        arena.food = vec![
+            Food {
+                pos: cell(10, 10),
+                kind: FoodKind::Regular,
+                expires_at_tick: None,
+            },
+            Food {
+                pos: cell(11, 12),
+                kind: FoodKind::Golden,
+                expires_at_tick: Some(250),
+            },
+        ];
+        arena.wreck_food = vec![cell(14, 14)];
+        arena.hazards.push(Hazard::Mine {
+            pos: cell(20, 5),
+            armed_at_tick: 100,
+            vanishes_at_tick: 400,
+        });
+        arena.inset = 1;
+        arena.next_shrink_tick = Some(300);
+
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let _: u64 = rng.gen();
+        let round = SavedRound {
+            players,
+            arena,
+            tick: 120,
+            elapsed: Duration::from_millis(15_500),
+            high_score: 9,
+            rng,
+            food_kind_rng: ChaCha12Rng::seed_from_u64(2),
+        };
+        (settings, round)
+    }
+
+    fn load(content: &str) -> Result<(GameSettings, SavedRound)> {
+        parse_saved_game(content, "savegame.txt", command_line_settings())
+    }
+
+    #[test]
+    fn saved_game_loads_as_it_was() {
+        let (settings, round) = saved_game();
+        let content = format_saved_game(&settings, &round);
+
+        let (loaded_settings, loaded_round) = load(&content).unwrap();
+
+        assert_eq!(loaded_round.players, round.players);
+        assert_eq!(loaded_round.arena, round.arena);
+        assert_eq!(loaded_round.rng, round.rng);
+        assert_eq!(loaded_round.food_kind_rng, round.food_kind_rng);
+        assert_eq!(loaded_settings.level, settings.level);
+        assert_eq!(loaded_settings.teams, settings.teams);
+        assert_eq!(loaded_settings.game_mode, settings.game_mode);
+        // everything else that is saved comes back as well
+        assert_eq!(format_saved_game(&loaded_settings, &loaded_round), content);
+        // how the game looks is up to the command line
+        assert_eq!(loaded_settings.render_mode, RenderMode::Ascii);
+    }
+
+    #[test]
+    fn rng_continues_where_it_was_saved() {
+        let mut rng = ChaCha12Rng::from_entropy();
+        let _: [u32; 5] = rng.gen();
+        let mut restored =
+            parse_rng(&format_rng(&rng).split_whitespace().collect::<Vec<&str>>()).unwrap();
+
+        assert_eq!(restored.gen::<[u64; 8]>(), rng.gen::<[u64; 8]>());
+    }
+
+    #[test]
+    fn malformed_saved_games_are_rejected() {
+        let (settings, round) = saved_game();
+        let content = format_saved_game(&settings, &round);
+        let without_line = |prefix: &str| {
+            content
+                .lines()
+                .filter(|line| !line.starts_with(prefix))
+                .map(|line| format!("{}\n", line))
+                .collect::<String>()
+        };
+        let cases = [
+            ("players = 2", "players = 0"),
+            ("players = 2", "players = 5"),
+            ("speed = 12", "speed = 1000"),
+            ("speed = 12", "speed = NaN"),
+            ("mode = battle-royale 30", "mode = battle-royale 0"),
+            ("mode = battle-royale 30", "mode = target-length 3"),
+            ("mode = battle-royale 30", "mode = time-attack 60"),
+            ("food = 2", "food = 0"),
+            ("food = 2", "food = 70000"),
+            ("golden_food = 0.25", "golden_food = 1.5"),
+            ("growth = 2", "growth = 0"),
+            ("mines = 1", "mines = -1"),
+            ("teams = 2,1", "teams = 2,9"),
+            ("teams = 2,1", "teams = 1,2"),
+            ("steering = hybrid,two-key", "steering = hybrid"),
+            ("arena = 50x30", "arena = 20x20"),
+            ("arena = 50x30", "arena = 100000x100000"),
+            ("level = 50x30", "level = 60x30"),
+            ("level_walls = 5,30 5,31", "level_walls = 13,12"),
+            ("wreck_food = 14,14", "wreck_food = 14,50"),
+            ("inset = 1", "inset = 14"),
+            ("inset = 1", "inset = 12"),
+            ("tick = 120", "tick = soon"),
+            ("tick = 120", "colour = green"),
+            ("tick = 120", "tick"),
+        ];
+        for (from, to) in cases {
+            assert!(content.contains(from), "{}", from);
+            let result = load(&content.replacen(from, to, 1));
+            assert_eq!(
+                result.err().map(|err| err.kind()),
+                Some(ErrorKind::InvalidData),
+                "{}",
+                to
+            );
+        }
+
+        for prefix in ["rng", "snake", "level =", "food_kind_rng"] {
+            let result = load(&without_line(prefix));
+            assert_eq!(
+                result.err().map(|err| err.kind()),
+                Some(ErrorKind::InvalidData),
+                "without {}",
+                prefix
+            );
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::cmp::Reverse;
+use std::env;
+use std::fs;
+use std::io::Result;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Entries kept per high score table.
+const MAX_ENTRIES_PER_TABLE: usize = 10;
+const SCORES_FILE_NAME: &str = "scores.txt";
+
+/// Order of the entries in a high score table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ranking {
+    /// e.g. points, more is better
+    HighestFirst,
+    /// e.g. times, less is better
+    LowestFirst,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreEntry {
+    /// name of the table the entry belongs to, e.g. "time-attack-60"
+    pub table: String,
+    pub value: u64,
+    /// seconds since the unix epoch
+    pub recorded_at: u64,
+}
+
+/// High score tables of the challenge modes, kept across sessions in a text file
+/// with one `table value recorded_at` entry per line.
+pub struct ScoreTables {
+    /// `None` if there is no place to keep the scores
+    path: Option<PathBuf>,
+    entries: Vec<ScoreEntry>,
+}
+
+impl ScoreTables {
+    /// Loads the high scores of previous sessions. A missing or unreadable file
+    /// starts empty tables, invalid lines are skipped.
+    pub fn load() -> ScoreTables {
+        let path = data_dir().map(|dir| dir.join(SCORES_FILE_NAME));
+        let content = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        let entries = content
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                Some(ScoreEntry {
+                    table: fields.next()?.to_string(),
+                    value: fields.next()?.parse().ok()?,
+                    recorded_at: fields.next()?.parse().ok()?,
+                })
+            })
+            .collect();
+        ScoreTables { path, entries }
+    }
+
+    /// Returns the entries of a table, best first.
+    pub fn table(&self, table: &str, ranking: Ranking) -> Vec<&ScoreEntry> {
+        let mut entries: Vec<&ScoreEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.table == table)
+            .collect();
+        // among equal values, the older entry stays ahead
+        entries.sort_by_key(|entry| entry.recorded_at);
+        match ranking {
+            Ranking::HighestFirst => entries.sort_by_key(|entry| Reverse(entry.value)),
+            Ranking::LowestFirst => entries.sort_by_key(|entry| entry.value),
+        }
+        entries
+    }
+
+    pub fn best(&self, table: &str, ranking: Ranking) -> Option<u64> {
+        self.table(table, ranking).first().map(|entry| entry.value)
+    }
+
+    /// Adds a result to a table and saves all tables. Returns the rank of the result,
+    /// or `None` if it is not good enough to be kept.
+    pub fn record(&mut self, table: &str, value: u64, ranking: Ranking) -> Result<Option<usize>> {
+        let entry = ScoreEntry {
+            table: table.to_string(),
+            value,
+            recorded_at: unix_time(),
+        };
+        self.entries.push(entry.clone());
+
+        let kept: Vec<ScoreEntry> = self
+            .table(table, ranking)
+            .into_iter()
+            .take(MAX_ENTRIES_PER_TABLE)
+            .cloned()
+            .collect();
+        let rank = kept.iter().position(|kept_entry| *kept_entry == entry);
+        self.entries.retain(|entry| entry.table != table);
+        self.entries.extend(kept);
+
+        self.save()?;
+        Ok(rank)
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut content = "# high scores of rs_snake: table value recorded_at\n".to_string();
+        for entry in &self.entries {
+            content.push_str(&format!(
+                "{} {} {}\n",
+                entry.table, entry.value, entry.recorded_at
+            ));
+        }
+        fs::write(path, content)
+    }
+}
+
+/// Directory the game keeps its files in, `%APPDATA%\rs_snake` on Windows and
+/// `~/.rs_snake` elsewhere.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(app_data) = env::var_os("APPDATA") {
+        return Some(PathBuf::from(app_data).join("rs_snake"));
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".rs_snake"))
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
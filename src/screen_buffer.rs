@@ -2,70 +2,322 @@ use std::io::Write;
 
 use crossterm::{
     cursor::{self},
-    style::{self, StyledContent, Stylize},
-    QueueableCommand,
+    style::{self, Color, StyledContent, Stylize},
+    terminal, QueueableCommand,
 };
 
 use std::io::Result;
 
-#[derive(Clone, Copy, Debug)]
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::theme::{Theme, NUM_PORTAL_COLORS};
+
+/// Sides of a snake segment that connect to its neighbouring segments.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SegmentLinks {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl SegmentLinks {
+    /// Links the segment at `pos` to the one at `neighbour`, if they are adjacent.
+    pub fn add_link(&mut self, pos: &Coordinate, neighbour: &Coordinate) {
+        if neighbour.col == pos.col && neighbour.row + 1 == pos.row {
+            self.up = true;
+        } else if neighbour.col == pos.col && neighbour.row == pos.row + 1 {
+            self.down = true;
+        } else if neighbour.row == pos.row && neighbour.col + 1 == pos.col {
+            self.left = true;
+        } else if neighbour.row == pos.row && neighbour.col == pos.col + 1 {
+            self.right = true;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum GameContent {
-    SnakeHead(usize),
-    SnakeBody(usize),
+    /// head of a snake of the given team
+    SnakeHead(usize, SegmentLinks),
+    /// body of a snake of the given team
+    SnakeBody(usize, SegmentLinks),
     Food,
+    GoldenFood,
+    /// end of the portal with the given index
+    Portal(usize),
+    /// patrolling block, closed gate or mine
+    Hazard,
     Border,
+    Obstacle,
     Empty,
-    Character(char),
-    CharacterOnBorder(char),
 }
 
-fn map_game_content_to_color(gc: &GameContent, is_padded_char: bool) -> StyledContent<String> {
-    // \u{2588} is a full block symbol
+/// Horizontal placement of a line of text within the screen buffer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// A line of text, kept apart from the game content so that it can be drawn at the
+/// full resolution of the terminal instead of one character per cell.
+#[derive(Clone, PartialEq)]
+struct TextLine {
+    row: usize,
+    alignment: TextAlignment,
+    /// characters together with the number of terminal columns they take up,
+    /// combining characters are merged into the character they belong to
+    clusters: Vec<(String, usize)>,
+}
+
+impl TextLine {
+    fn width(&self) -> usize {
+        self.clusters.iter().map(|(_, width)| width).sum()
+    }
+}
+
+fn split_into_clusters(text: &str) -> Vec<(String, usize)> {
+    let mut clusters: Vec<(String, usize)> = vec![];
+    for sym in text.chars() {
+        // control characters have no width and would mess up the terminal
+        let width = match sym.width() {
+            Some(width) => width,
+            None => continue,
+        };
+        match clusters.last_mut() {
+            Some((cluster, _)) if width == 0 => cluster.push(sym),
+            _ if width == 0 => {}
+            _ => clusters.push((sym.to_string(), width)),
+        }
+    }
+    clusters
+}
+
+/// Shortens the text to the given number of terminal columns, ending it with an
+/// ellipsis if anything had to be cut off.
+fn truncate_clusters(mut clusters: Vec<(String, usize)>, max_width: usize) -> Vec<(String, usize)> {
+    let total_width: usize = clusters.iter().map(|(_, width)| width).sum();
+    if total_width <= max_width {
+        return clusters;
+    }
+
+    let mut width = 0;
+    let num_kept = clusters
+        .iter()
+        .take_while(|(_, cluster_width)| {
+            width += cluster_width;
+            width < max_width
+        })
+        .count();
+    clusters.truncate(num_kept);
+    if max_width > 0 {
+        clusters.push(("\u{2026}".to_string(), 1));
+    }
+    clusters
+}
+
+/// Width of the side panel next to the arena, in terminal columns.
+const SIDE_PANEL_WIDTH: usize = 28;
+/// Empty columns between the arena and the side panel.
+const SIDE_PANEL_GAP: usize = 2;
+
+/// A line of the side panel shown next to the arena.
+#[derive(Clone, PartialEq)]
+pub struct PanelLine {
+    pub text: String,
+    /// draws the line in the color of the given team instead of the text color
+    pub team_idx: Option<usize>,
+}
+
+impl PanelLine {
+    pub fn new(text: String) -> PanelLine {
+        PanelLine {
+            text,
+            team_idx: None,
+        }
+    }
+
+    pub fn for_team(text: String, team_idx: usize) -> PanelLine {
+        PanelLine {
+            text,
+            team_idx: Some(team_idx),
+        }
+    }
+}
+
+/// Returns the color of a cell when drawn as a block.
+fn map_game_content_to_block_color(gc: &GameContent, theme: &Theme) -> Color {
     match gc {
-        GameContent::SnakeHead(player_idx) => {
-            if *player_idx == 0_usize {
-                "\u{2588}".to_string().dark_green()
-            } else {
-                "\u{2588}".to_string().dark_yellow()
-            }
+        GameContent::SnakeHead(team_idx, _) => theme.snake_heads[*team_idx],
+        GameContent::SnakeBody(team_idx, _) => theme.snake_bodies[*team_idx],
+        GameContent::Food => theme.food,
+        GameContent::GoldenFood => theme.golden_food,
+        GameContent::Portal(portal_idx) => theme.portals[portal_idx % NUM_PORTAL_COLORS],
+        GameContent::Hazard => theme.hazard,
+        GameContent::Border => theme.border,
+        GameContent::Obstacle => theme.obstacle,
+        GameContent::Empty => theme.background,
+    }
+}
+
+fn map_game_content_to_color(gc: &GameContent, theme: &Theme) -> StyledContent<String> {
+    // \u{2588} is a full block symbol
+    "\u{2588}"
+        .to_string()
+        .with(map_game_content_to_block_color(gc, theme))
+}
+
+/// Draws snakes with line characters that follow their shape, pointing the head in the
+/// direction of movement and tapering off at the tail. Vertical lines run through the
+/// second of the two columns of a cell, so that corners can connect in both directions.
+fn map_game_content_to_directional(
+    gc: &GameContent,
+    is_padded_char: bool,
+    theme: &Theme,
+) -> StyledContent<String> {
+    let (symbols, color) = match gc {
+        GameContent::SnakeHead(team_idx, links) => {
+            let symbols = match (links.up, links.down, links.left, links.right) {
+                (false, true, false, false) => " \u{25b2}",
+                (true, false, false, false) => " \u{25bc}",
+                (false, false, false, true) => "\u{25c0}\u{2501}",
+                (false, false, true, false) => "\u{2501}\u{25b6}",
+                _ => "\u{2588}\u{2588}",
+            };
+            (symbols, theme.snake_heads[*team_idx])
         }
-        GameContent::SnakeBody(player_idx) => {
-            if *player_idx == 0_usize {
-                "\u{2588}".to_string().green()
-            } else {
-                "\u{2588}".to_string().yellow()
-            }
+        GameContent::SnakeBody(team_idx, links) => {
+            let symbols = match (links.up, links.down, links.left, links.right) {
+                (true, true, false, false) => " \u{2503}",
+                (false, false, true, true) => "\u{2501}\u{2501}",
+                (true, false, false, true) => " \u{2517}",
+                (true, false, true, false) => "\u{2501}\u{251b}",
+                (false, true, false, true) => " \u{250f}",
+                (false, true, true, false) => "\u{2501}\u{2513}",
+                // tails only connect to a single neighbour
+                (true, false, false, false) => " \u{2579}",
+                (false, true, false, false) => " \u{257b}",
+                (false, false, true, false) => "\u{2578} ",
+                (false, false, false, true) => " \u{257a}",
+                _ => "\u{2588}\u{2588}",
+            };
+            (symbols, theme.snake_bodies[*team_idx])
         }
-        GameContent::Food => "\u{2588}".to_string().red(),
-        GameContent::Border => "\u{2588}".to_string().dark_blue(),
-        GameContent::Empty => "\u{2588}".to_string().black(),
-        GameContent::Character(some_char) => {
-            if is_padded_char {
-                "\u{2588}".to_string().black()
-            } else {
-                some_char.to_string().white().on_black()
-            }
+        _ => return map_game_content_to_color(gc, theme),
+    };
+
+    let symbol = symbols.chars().nth(is_padded_char as usize).unwrap();
+    symbol.to_string().with(color).on(theme.background)
+}
+
+/// Draws two vertically stacked cells as one terminal character, using the upper half
+/// block \u{2580} in the color of the top cell on the background color of the bottom cell.
+fn map_game_content_to_half_block(
+    top: &GameContent,
+    bottom: &GameContent,
+    theme: &Theme,
+) -> StyledContent<String> {
+    "\u{2580}"
+        .to_string()
+        .with(map_game_content_to_block_color(top, theme))
+        .on(map_game_content_to_block_color(bottom, theme))
+}
+
+/// Draws game content as ASCII glyphs, so that the game stays readable without
+/// colors or unicode block characters. Snakes are told apart by their team letter.
+fn map_game_content_to_glyph(
+    gc: &GameContent,
+    is_padded_char: bool,
+    theme: &Theme,
+) -> StyledContent<String> {
+    let glyph = |symbol: char| {
+        if is_padded_char {
+            " ".to_string()
+        } else {
+            symbol.to_string()
         }
-        GameContent::CharacterOnBorder(some_char) => {
-            if is_padded_char {
-                "\u{2588}".to_string().dark_blue()
-            } else {
-                some_char.to_string().white().on_dark_blue()
-            }
+    };
+    let team_letter = |team_idx: usize| (b'a' + team_idx as u8) as char;
+    match gc {
+        GameContent::SnakeHead(team_idx, _) => glyph(team_letter(*team_idx).to_ascii_uppercase())
+            .with(theme.snake_heads[*team_idx])
+            .on(theme.background),
+        GameContent::SnakeBody(team_idx, _) => glyph(team_letter(*team_idx))
+            .with(theme.snake_bodies[*team_idx])
+            .on(theme.background),
+        GameContent::Food => glyph('*').with(theme.food).on(theme.background),
+        GameContent::GoldenFood => glyph('$').with(theme.golden_food).on(theme.background),
+        // both ends of a portal show the same digit
+        GameContent::Portal(portal_idx) => {
+            glyph(char::from_digit((*portal_idx as u32 + 1) % 10, 10).unwrap())
+                .with(theme.portals[portal_idx % NUM_PORTAL_COLORS])
+                .on(theme.background)
         }
+        // borders are drawn without gaps, so that they form continuous lines
+        GameContent::Border => "#".to_string().with(theme.border).on(theme.background),
+        GameContent::Hazard => glyph('!').with(theme.hazard).on(theme.background),
+        GameContent::Obstacle => glyph('X').with(theme.obstacle).on(theme.background),
+        GameContent::Empty => " ".to_string().on(theme.background),
     }
 }
 
+/// How game content is turned into characters on the terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    /// colored full block characters
+    Blocks,
+    /// colored half block characters, packing two rows into every line of the terminal
+    HalfBlocks,
+    /// plain ASCII glyphs, see `map_game_content_to_glyph`
+    Ascii,
+    /// like `Blocks`, but snakes are drawn with box drawing characters that show
+    /// their direction, see `map_game_content_to_directional`
+    Directional,
+}
+
+impl RenderMode {
+    /// Picks a render mode that is readable in the current terminal. Blocks can only be
+    /// told apart by their color, and the plain linux console lacks most unicode glyphs.
+    pub fn detect(is_color_enabled: bool) -> RenderMode {
+        let term = std::env::var("TERM").unwrap_or_default();
+        if !is_color_enabled || term == "dumb" || term == "linux" {
+            RenderMode::Ascii
+        } else {
+            RenderMode::Blocks
+        }
+    }
+}
+
+/// Returns false if the user or the terminal asked for output without colors.
+pub fn is_color_supported() -> bool {
+    let is_no_color_set = std::env::var("NO_COLOR").is_ok_and(|v| !v.is_empty());
+    let is_dumb_terminal = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+    !is_no_color_set && !is_dumb_terminal
+}
+
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub struct Coordinate {
     pub row: usize,
     pub col: usize,
 }
 
+#[derive(Clone)]
 pub struct ScreenBuffer {
     screen_width: usize,
     screen_height: usize,
     buffer: Vec<GameContent>,
+    text_lines: Vec<TextLine>,
+    side_panel: Vec<PanelLine>,
+    theme: Theme,
+    render_mode: RenderMode,
+    /// what is currently on the terminal, so that only changes have to be drawn;
+    /// the cells are empty if nothing is known to be there
+    drawn_buffer: Vec<GameContent>,
+    drawn_text_lines: Vec<TextLine>,
+    /// side panel together with the terminal width it was drawn for
+    drawn_side_panel: Option<(Vec<PanelLine>, usize)>,
 }
 
 impl ScreenBuffer {
@@ -73,11 +325,20 @@ impl ScreenBuffer {
         screen_width: usize,
         screen_height: usize,
         initial_content: GameContent,
+        theme: Theme,
+        render_mode: RenderMode,
     ) -> ScreenBuffer {
         ScreenBuffer {
             screen_height,
             screen_width,
             buffer: vec![initial_content; screen_height * screen_width],
+            text_lines: vec![],
+            side_panel: vec![],
+            theme,
+            render_mode,
+            drawn_buffer: vec![],
+            drawn_text_lines: vec![],
+            drawn_side_panel: None,
         }
     }
 
@@ -85,24 +346,96 @@ impl ScreenBuffer {
         for screen_char in &mut self.buffer {
             *screen_char = content;
         }
+        self.text_lines.clear();
+        self.side_panel.clear();
+    }
+
+    /// Shows the given lines next to the arena, if the terminal is wide enough.
+    pub fn set_side_panel(&mut self, lines: Vec<PanelLine>) {
+        self.side_panel = lines;
+    }
+
+    /// Number of terminal columns used to draw a single cell.
+    fn columns_per_cell(&self) -> usize {
+        match self.render_mode {
+            RenderMode::HalfBlocks => 1,
+            _ => 2,
+        }
+    }
+
+    /// Number of cells drawn on top of each other in a single line of the terminal.
+    fn rows_per_line(&self) -> usize {
+        match self.render_mode {
+            RenderMode::HalfBlocks => 2,
+            _ => 1,
+        }
+    }
+
+    /// Maps a position on the terminal back to the cell of the arena drawn there.
+    pub fn cell_at(&self, column: u16, line: u16) -> Option<Coordinate> {
+        let cell = Coordinate {
+            row: line as usize * self.rows_per_line(),
+            col: column as usize / self.columns_per_cell(),
+        };
+        (cell.row < self.screen_height && cell.col < self.screen_width).then_some(cell)
+    }
+
+    /// Number of terminal columns available for a line of text, leaving space for the
+    /// border on both sides.
+    fn text_width(&self) -> usize {
+        (self.screen_width - 2) * self.columns_per_cell()
+    }
+
+    /// Places a line of text on the given row, replacing any text with the same alignment
+    /// on that row. Text that does not fit is truncated.
+    pub fn set_text_at_row(&mut self, target_row: usize, message: &str, alignment: TextAlignment) {
+        self.text_lines
+            .retain(|line| line.row != target_row || line.alignment != alignment);
+        if target_row >= self.screen_height {
+            return;
+        }
+        self.text_lines.push(TextLine {
+            row: target_row,
+            alignment,
+            clusters: truncate_clusters(split_into_clusters(message), self.text_width()),
+        });
     }
 
     pub fn set_centered_text_at_row(&mut self, target_row: usize, message: &str) {
-        let str_chars = message.chars();
-        let str_len = str_chars.clone().count();
-        let header_start_idx = ((self.screen_width - str_len) / 2usize).max(0);
+        self.set_text_at_row(target_row, message, TextAlignment::Center);
+    }
 
-        let mut col_idx = header_start_idx;
+    /// Places the text starting at the given row, breaking it into several lines at
+    /// whitespace if it is too wide. Returns the number of rows taken up.
+    pub fn set_wrapped_text_at_row(
+        &mut self,
+        first_row: usize,
+        message: &str,
+        alignment: TextAlignment,
+    ) -> usize {
+        let max_width = self.text_width();
+        let mut lines: Vec<String> = vec![];
+        let mut line_width = 0;
+        for word in message.split_whitespace() {
+            let word_width = word.width();
+            match lines.last_mut() {
+                Some(line) if line_width + 1 + word_width <= max_width => {
+                    line.push(' ');
+                    line.push_str(word);
+                    line_width += 1 + word_width;
+                }
+                _ => {
+                    lines.push(word.to_string());
+                    line_width = word_width;
+                }
+            }
+        }
 
-        for sym in str_chars {
-            let gc = if target_row == 0 {
-                GameContent::CharacterOnBorder(sym)
-            } else {
-                GameContent::Character(sym)
-            };
-            self.set_at(target_row, col_idx, gc);
-            col_idx += 1;
+        let rows_per_line = self.rows_per_line();
+        for (line_idx, line) in lines.iter().enumerate() {
+            self.set_text_at_row(first_row + line_idx * rows_per_line, line, alignment);
         }
+        lines.len() * rows_per_line
     }
 
     pub fn get_at(&self, row: usize, col: usize) -> GameContent {
@@ -113,33 +446,274 @@ impl ScreenBuffer {
         self.buffer[col + row * self.screen_width] = content;
     }
 
-    pub fn add_border(&mut self, border_symbol: GameContent) {
+    /// Draws a border that is the given number of cells thick along the edges.
+    pub fn add_border(&mut self, border_symbol: GameContent, thickness: usize) {
         for row in 0..self.screen_height {
-            self.set_at(row, 0, border_symbol);
-            self.set_at(row, self.screen_width - 1, border_symbol);
+            for col in 0..self.screen_width {
+                let distance_to_edge = row
+                    .min(col)
+                    .min(self.screen_height - 1 - row)
+                    .min(self.screen_width - 1 - col);
+                if distance_to_edge < thickness {
+                    self.set_at(row, col, border_symbol);
+                }
+            }
         }
-        for col in 0..self.screen_width {
-            self.set_at(0, col, border_symbol);
-            self.set_at(self.screen_height - 1, col, border_symbol);
+    }
+
+    /// Forgets what was drawn, so that the next call to [`ScreenBuffer::draw`] draws
+    /// everything again, e.g. after the terminal was cleared.
+    pub fn invalidate(&mut self) {
+        self.drawn_buffer.clear();
+        self.drawn_text_lines.clear();
+        self.drawn_side_panel = None;
+    }
+
+    /// Draws everything that changed since the last call. Lines of the terminal showing
+    /// text are drawn completely, as changed cells would cover the text.
+    pub fn draw(&mut self, stdout: &mut std::io::Stdout) -> Result<()> {
+        let is_drawn = self.drawn_buffer.len() == self.buffer.len();
+        let rows_per_line = self.rows_per_line();
+        for line_idx in 0..self.screen_height.div_ceil(rows_per_line) {
+            let is_on_line = |line: &&TextLine| line.row / rows_per_line == line_idx;
+            let text: Vec<&TextLine> = self.text_lines.iter().filter(is_on_line).collect();
+            let drawn_text: Vec<&TextLine> =
+                self.drawn_text_lines.iter().filter(is_on_line).collect();
+            let is_text_changed = text.len() != drawn_text.len()
+                || text.iter().any(|line| !drawn_text.contains(line));
+
+            let rows =
+                line_idx * rows_per_line..((line_idx + 1) * rows_per_line).min(self.screen_height);
+            let changed_cols: Vec<usize> = (0..self.screen_width)
+                .filter(|&col| {
+                    !is_drawn
+                        || rows.clone().any(|row| {
+                            let idx = col + row * self.screen_width;
+                            self.buffer[idx] != self.drawn_buffer[idx]
+                        })
+                })
+                .collect();
+
+            let has_text = !text.is_empty() || !drawn_text.is_empty();
+            if has_text && (is_text_changed || !changed_cols.is_empty()) {
+                for col in 0..self.screen_width {
+                    self.queue_cell(stdout, line_idx, col)?;
+                }
+                for line in text {
+                    self.queue_text_line(stdout, line)?;
+                }
+            } else {
+                for col in changed_cols {
+                    self.queue_cell(stdout, line_idx, col)?;
+                }
+            }
+        }
+        self.drawn_buffer.clone_from(&self.buffer);
+        self.drawn_text_lines.clone_from(&self.text_lines);
+
+        let terminal_columns = terminal::size().map_or(0, |(cols, _)| cols as usize);
+        let is_panel_drawn = matches!(
+            &self.drawn_side_panel,
+            Some((lines, columns)) if *columns == terminal_columns && *lines == self.side_panel
+        );
+        if !is_panel_drawn {
+            self.queue_side_panel(stdout, terminal_columns)?;
+            self.drawn_side_panel = Some((self.side_panel.clone(), terminal_columns));
         }
+
+        stdout.flush()?;
+        Ok(())
     }
 
-    pub fn draw(&self, stdout: &mut std::io::Stdout) -> Result<()> {
-        for row_idx in 0..self.screen_height {
-            for col_idx_buffer in 0..self.screen_width {
-                let content = self.get_at(row_idx, col_idx_buffer);
-                // draw each element twice horizontally, so that we get square "pixels"
-                for i in 0..2 {
-                    let col_idx = 2 * col_idx_buffer + i;
+    /// Queues the cell in the given column of a line of the terminal, which in half block
+    /// mode covers two rows of cells.
+    fn queue_cell(
+        &self,
+        stdout: &mut std::io::Stdout,
+        line_idx: usize,
+        col_idx: usize,
+    ) -> Result<()> {
+        if self.render_mode == RenderMode::HalfBlocks {
+            let row_idx = line_idx * 2;
+            let top = self.get_at(row_idx, col_idx);
+            let bottom = if row_idx + 1 < self.screen_height {
+                self.get_at(row_idx + 1, col_idx)
+            } else {
+                GameContent::Empty
+            };
+
+            let styled_content = map_game_content_to_half_block(&top, &bottom, &self.theme);
+            stdout
+                .queue(cursor::MoveTo(col_idx as u16, line_idx as u16))?
+                .queue(style::PrintStyledContent(styled_content))?;
+            return Ok(());
+        }
 
-                    let styled_content = map_game_content_to_color(&content, i != 0);
+        let content = self.get_at(line_idx, col_idx);
+        // draw each element twice horizontally, so that we get square "pixels"
+        for i in 0..2 {
+            let is_padded_char = i != 0;
+            let styled_content = match self.render_mode {
+                RenderMode::Blocks | RenderMode::HalfBlocks => {
+                    map_game_content_to_color(&content, &self.theme)
+                }
+                RenderMode::Ascii => {
+                    map_game_content_to_glyph(&content, is_padded_char, &self.theme)
+                }
+                RenderMode::Directional => {
+                    map_game_content_to_directional(&content, is_padded_char, &self.theme)
+                }
+            };
+            stdout
+                .queue(cursor::MoveTo((2 * col_idx + i) as u16, line_idx as u16))?
+                .queue(style::PrintStyledContent(styled_content))?;
+        }
+        Ok(())
+    }
+
+    fn queue_text_line(&self, stdout: &mut std::io::Stdout, line: &TextLine) -> Result<()> {
+        let columns_per_cell = self.columns_per_cell();
+        let total_width = self.screen_width * columns_per_cell;
+        let mut col_idx = match line.alignment {
+            TextAlignment::Left => columns_per_cell,
+            TextAlignment::Center => (total_width - line.width()) / 2,
+            TextAlignment::Right => total_width - columns_per_cell - line.width(),
+        };
+        let line_idx = line.row / self.rows_per_line();
+
+        for (cluster, width) in &line.clusters {
+            // text takes on the color of the cell it is drawn on
+            let background = match self.render_mode {
+                RenderMode::Ascii => self.theme.background,
+                _ => map_game_content_to_block_color(
+                    &self.get_at(line.row, col_idx / columns_per_cell),
+                    &self.theme,
+                ),
+            };
+            stdout
+                .queue(cursor::MoveTo(col_idx as u16, line_idx as u16))?
+                .queue(style::PrintStyledContent(
+                    cluster.clone().with(self.theme.text).on(background),
+                ))?;
+            col_idx += width;
+        }
+        Ok(())
+    }
+
+    fn queue_side_panel(
+        &self,
+        stdout: &mut std::io::Stdout,
+        terminal_columns: usize,
+    ) -> Result<()> {
+        let arena_columns = self.screen_width * self.columns_per_cell();
+        let num_lines = self.screen_height.div_ceil(self.rows_per_line());
+
+        if self.side_panel.is_empty()
+            || terminal_columns < arena_columns + SIDE_PANEL_GAP + SIDE_PANEL_WIDTH
+        {
+            // remove what is left of a previously shown panel
+            if terminal_columns > arena_columns {
+                for line_idx in 0..num_lines {
                     stdout
-                        .queue(cursor::MoveTo(col_idx as u16, row_idx as u16))?
-                        .queue(style::PrintStyledContent(styled_content))?;
+                        .queue(cursor::MoveTo(arena_columns as u16, line_idx as u16))?
+                        .queue(terminal::Clear(terminal::ClearType::UntilNewLine))?;
                 }
             }
+            return Ok(());
+        }
+
+        for line_idx in 0..num_lines {
+            let (text, color) = match self.side_panel.get(line_idx) {
+                Some(line) => {
+                    let clusters =
+                        truncate_clusters(split_into_clusters(&line.text), SIDE_PANEL_WIDTH);
+                    let width: usize = clusters.iter().map(|(_, width)| width).sum();
+                    let mut text: String = clusters.into_iter().map(|(c, _)| c).collect();
+                    text.push_str(&" ".repeat(SIDE_PANEL_WIDTH - width));
+                    let color = match line.team_idx {
+                        Some(team_idx) => self.theme.snake_bodies[team_idx],
+                        None => self.theme.text,
+                    };
+                    (text, color)
+                }
+                None => (" ".repeat(SIDE_PANEL_WIDTH), self.theme.text),
+            };
+            stdout
+                .queue(cursor::MoveTo(
+                    (arena_columns + SIDE_PANEL_GAP) as u16,
+                    line_idx as u16,
+                ))?
+                .queue(style::PrintStyledContent(
+                    text.with(color).on(self.theme.background),
+                ))?;
         }
-        stdout.flush()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDE_TEXTS: [&str; 3] = ["日本語のテキスト", "🐍🐍🐍 snake 🍎", "a蛇b🐍c"];
+
+    fn width(clusters: &[(String, usize)]) -> usize {
+        clusters.iter().map(|(_, width)| width).sum()
+    }
+
+    /// Every cluster is a whole character of the text, with the width it had before.
+    fn assert_nothing_split(clusters: &[(String, usize)], text: &str) {
+        let whole = split_into_clusters(text);
+        for cluster in clusters {
+            assert!(
+                whole.contains(cluster) || cluster.0 == "\u{2026}",
+                "{:?} of {:?}",
+                cluster,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn truncating_keeps_wide_characters_whole() {
+        for text in WIDE_TEXTS {
+            let total_width = text.width();
+            for max_width in 0..total_width + 3 {
+                let clusters = truncate_clusters(split_into_clusters(text), max_width);
+                assert!(width(&clusters) <= max_width, "{:?} in {}", text, max_width);
+                assert_nothing_split(&clusters, text);
+                if max_width >= total_width {
+                    assert_eq!(clusters, split_into_clusters(text));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrapping_keeps_wide_characters_whole() {
+        // 2 columns per cell, so 20 columns of text between the borders: ten snakes fill
+        // a row exactly, while eleven of them or the longest Japanese word are too wide
+        let mut screen_buffer = ScreenBuffer::new(
+            12,
+            40,
+            GameContent::Empty,
+            Theme::default(),
+            RenderMode::Ascii,
+        );
+        let max_width = screen_buffer.text_width();
+        let texts = [
+            "日本語 テキスト 折り返し 日本語のテキストは長い 終わり",
+            "🐍🐍🐍🐍🐍🐍🐍🐍🐍🐍 🐍🐍🐍🐍🐍🐍🐍🐍🐍🐍🐍 🍎",
+            "ｆｕｌｌｗｉｄｔｈ ｔｅｘｔ ａｔ ｔｈｅ ｅｄｇｅ ｏｆ ｔｈｅ ｒｏｗ",
+        ];
+        for text in texts {
+            screen_buffer.set_all(GameContent::Empty);
+            let num_rows = screen_buffer.set_wrapped_text_at_row(1, text, TextAlignment::Center);
+            assert_eq!(screen_buffer.text_lines.len(), num_rows, "{:?}", text);
+            for line in &screen_buffer.text_lines {
+                assert!(line.width() <= max_width, "{:?}", text);
+                assert_nothing_split(&line.clusters, text);
+            }
+        }
+    }
+}
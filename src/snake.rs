@@ -1,52 +1,342 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::collections::VecDeque;
 use std::io::stdout;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crossterm::{
-    cursor::{self},
-    event::{KeyCode, KeyEvent},
-    terminal::{self, disable_raw_mode, enable_raw_mode},
+    event::KeyCode,
+    terminal::{self},
     ExecutableCommand,
 };
 
 use std::io::Result;
 
-use crate::events::{send_events, KeyEventQueue};
-use crate::screen_buffer::{Coordinate, GameContent, ScreenBuffer};
+use crate::animation::{countdown_bar, Animations, ANIMATION_FRAME_TIME};
+use crate::arena::{Arena, FoodKind, SpawnPolicy};
+use crate::daily::DailyChallenge;
+use crate::events::{Action, GameEvent, InputEvents, SteeringKey};
+use crate::level::Level;
+use crate::savegame::{remove_saved_game, save_game, SavedRound};
+use crate::scores::{Ranking, ScoreTables};
+use crate::screen_buffer::{
+    Coordinate, GameContent, PanelLine, RenderMode, ScreenBuffer, SegmentLinks, TextAlignment,
+};
+use crate::terminal::{install_panic_hook, register_stop_signals, TerminalGuard};
+use crate::theme::Theme;
+
+/// Maximum number of players that can share one keyboard.
+pub const MAX_PLAYERS: usize = 4;
+
+/// Moves per second at normal difficulty.
+pub const NORMAL_SPEED: f64 = 8.0;
+/// With length based scoring, food is worth its points once more for every this many segments.
+const LENGTH_PER_SCORE_MULTIPLIER: usize = 10;
+
+/// Smallest arena that still fits all snakes and the start screen.
+pub const MIN_ARENA_WIDTH: usize = 40;
+pub const MIN_ARENA_HEIGHT: usize = 30;
+/// Largest arena, which keeps every cell addressable by the cursor of the terminal.
+pub const MAX_ARENA_WIDTH: usize = 1000;
+pub const MAX_ARENA_HEIGHT: usize = 1000;
+
+/// How often the screen is refreshed while the game is paused.
+const PAUSED_REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the countdown before the next round is updated.
+const RESTART_COUNTDOWN_STEP: Duration = Duration::from_millis(100);
+/// Ticks that are late by more than this are skipped instead of being caught up.
+const MAX_TICK_LAG: Duration = Duration::from_millis(250);
+/// Turns a player can type ahead of the snake.
+const MAX_QUEUED_KEYS: usize = 3;
+/// Ticks the next ring of the border blinks before the arena shrinks.
+const SHRINK_WARNING_TICKS: u64 = 24;
+
+/// Decides when a round ends and who wins it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    /// the round ends as soon as any snake crashes
+    Classic,
+    /// crashed snakes are removed, the round ends when only one snake remains
+    LastSnakeStanding,
+    /// like LastSnakeStanding, but when time runs out the longest surviving snake wins
+    Timed(Duration),
+    /// like LastSnakeStanding, but the border advances inwards after every interval
+    /// and eliminates the snakes it catches
+    BattleRoyale(Duration),
+    /// single player challenge: eat as much as possible before time runs out
+    TimeAttack(Duration),
+    /// single player challenge: reach the given length as fast as possible
+    TargetLength(usize),
+}
+
+impl GameMode {
+    /// Name and order of the high score table of the challenge modes,
+    /// `None` for the other modes.
+    pub fn score_table(self) -> Option<(String, Ranking)> {
+        match self {
+            GameMode::TimeAttack(time_limit) => Some((
+                format!("time-attack-{}", time_limit.as_secs()),
+                Ranking::HighestFirst,
+            )),
+            GameMode::TargetLength(target_length) => Some((
+                format!("target-length-{}", target_length),
+                Ranking::LowestFirst,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Formats an entry of the high score table, points or milliseconds.
+    pub fn format_record(self, value: u64) -> String {
+        match self {
+            GameMode::TargetLength(_) => format!("{:.1}s", value as f64 / 1000.0),
+            _ => value.to_string(),
+        }
+    }
+}
+
+/// What happens to the body of a snake that got eliminated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wreckage {
+    Vanish,
+    Obstacle,
+    Food,
+}
+
+/// How a player steers their snake.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SteeringMode {
+    /// one key for each direction
+    FourKeys,
+    /// two keys turning left and right relative to the heading of the snake
+    TwoKeys,
+    /// one key for each direction, plus two extra keys turning relative to the heading
+    Hybrid,
+}
+
+/// How many points eating food is worth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scoring {
+    /// just the points of the food
+    Flat,
+    /// the points of the food scaled by the speed of the game
+    Speed,
+    /// the points of the food scaled by the length of the snake, long snakes are risky
+    Length,
+}
+
+impl Scoring {
+    pub fn score(self, food_points: usize, moves_per_second: f64, snake_len: usize) -> usize {
+        match self {
+            Scoring::Flat => food_points,
+            Scoring::Speed => {
+                (food_points as f64 * moves_per_second / NORMAL_SPEED).ceil() as usize
+            }
+            Scoring::Length => food_points * (1 + snake_len / LENGTH_PER_SCORE_MULTIPLIER),
+        }
+    }
+}
+
+/// Why a round ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoundEnd {
+    /// snakes crashed until the game mode's end condition was met
+    Crash,
+    TimeUp,
+    /// there is no space left for new food
+    BoardFull,
+    /// a snake reached the length of the target length mode
+    TargetReached,
+}
+
+pub struct GameSettings {
+    pub num_players: usize,
+    pub arena_width: usize,
+    pub arena_height: usize,
+    pub target_fps: f64,
+    /// steering mode of every player
+    pub steering: Vec<SteeringMode>,
+    pub game_mode: GameMode,
+    pub wreckage: Wreckage,
+    /// number of pieces of food in the arena at the same time
+    pub food_count: usize,
+    pub food_spawn: SpawnPolicy,
+    /// time after which uneaten food moves somewhere else, `None` if it stays forever
+    pub food_lifetime: Option<Duration>,
+    /// chance of new food being golden
+    pub golden_food_chance: f64,
+    /// number of segments a snake grows by when eating regular food
+    pub base_growth: usize,
+    pub scoring: Scoring,
+    /// walls and portals of the arena, `None` for an empty arena
+    pub level: Option<Level>,
+    /// number of portals placed at random at the start of every round
+    pub random_portals: usize,
+    /// number of patrolling blocks placed at random at the start of every round
+    pub random_patrols: usize,
+    /// number of mines in the arena at the same time
+    pub mine_count: usize,
+    /// the challenge of the day, which plays the same random events in every round
+    pub daily: Option<DailyChallenge>,
+    /// team index of every player, `None` if everyone plays on their own
+    pub teams: Option<Vec<usize>>,
+    /// whether snakes may pass through the bodies of their teammates
+    pub pass_through_teammates: bool,
+    pub theme: Theme,
+    pub render_mode: RenderMode,
+    /// shows live stats next to the arena if the terminal is wide enough
+    pub show_side_panel: bool,
+    /// draw on the alternate screen, so the terminal contents come back after the game
+    pub use_alternate_screen: bool,
+    /// player 1 can also steer towards the cell clicked with the mouse
+    pub is_mouse_steering: bool,
+    /// turn on the kitty keyboard protocol, if the terminal supports it
+    pub use_keyboard_enhancement: bool,
+}
+
+impl GameSettings {
+    pub fn team_of_player(&self, player_idx: usize) -> usize {
+        match &self.teams {
+            Some(teams) => teams[player_idx],
+            None => player_idx,
+        }
+    }
+}
+
+struct PlayerControls {
+    left: KeyCode,
+    right: KeyCode,
+    up: KeyCode,
+    down: KeyCode,
+    /// extra keys turning relative to the heading in hybrid steering mode
+    turn_left: KeyCode,
+    turn_right: KeyCode,
+    four_key_help: &'static str,
+    two_key_help: &'static str,
+    turn_help: &'static str,
+}
+
+/// Saves the round in progress and exits, away from the keys the players steer with.
+const SAVE_KEY: KeyCode = KeyCode::F(2);
+
+impl PlayerControls {
+    fn steering_key(&self, key_code: KeyCode) -> Option<SteeringKey> {
+        if key_code == self.left {
+            Some(SteeringKey::Left)
+        } else if key_code == self.right {
+            Some(SteeringKey::Right)
+        } else if key_code == self.up {
+            Some(SteeringKey::Up)
+        } else if key_code == self.down {
+            Some(SteeringKey::Down)
+        } else if key_code == self.turn_left {
+            Some(SteeringKey::TurnLeft)
+        } else if key_code == self.turn_right {
+            Some(SteeringKey::TurnRight)
+        } else {
+            None
+        }
+    }
+}
+
+const PLAYER_CONTROLS: [PlayerControls; MAX_PLAYERS] = [
+    PlayerControls {
+        left: KeyCode::Left,
+        right: KeyCode::Right,
+        up: KeyCode::Up,
+        down: KeyCode::Down,
+        turn_left: KeyCode::Char(','),
+        turn_right: KeyCode::Char('.'),
+        four_key_help: "arrow keys",
+        two_key_help: "left and right arrow keys",
+        turn_help: ", and .",
+    },
+    PlayerControls {
+        left: KeyCode::Char('a'),
+        right: KeyCode::Char('d'),
+        up: KeyCode::Char('w'),
+        down: KeyCode::Char('s'),
+        turn_left: KeyCode::Char('z'),
+        turn_right: KeyCode::Char('c'),
+        four_key_help: "W A S D keys",
+        two_key_help: "A and D keys",
+        turn_help: "Z and C",
+    },
+    PlayerControls {
+        left: KeyCode::Char('j'),
+        right: KeyCode::Char('l'),
+        up: KeyCode::Char('i'),
+        down: KeyCode::Char('k'),
+        turn_left: KeyCode::Char('u'),
+        turn_right: KeyCode::Char('o'),
+        four_key_help: "I J K L keys",
+        two_key_help: "J and L keys",
+        turn_help: "U and O",
+    },
+    PlayerControls {
+        left: KeyCode::Char('4'),
+        right: KeyCode::Char('6'),
+        up: KeyCode::Char('8'),
+        down: KeyCode::Char('5'),
+        turn_left: KeyCode::Char('7'),
+        turn_right: KeyCode::Char('9'),
+        four_key_help: "8 4 5 6 keys",
+        two_key_help: "4 and 6 keys",
+        turn_help: "7 and 9",
+    },
+];
 
 pub struct SnakeGame {
-    num_players: usize,
-    target_fps: f64,
-    is_four_key_steering: bool,
+    settings: GameSettings,
+    /// round to continue with instead of starting a new one
+    saved_round: Option<SavedRound>,
 }
 
 impl SnakeGame {
-    pub fn new(num_players: usize, target_fps: f64, is_four_key_steering: bool) -> SnakeGame {
+    pub fn new(settings: GameSettings) -> SnakeGame {
         SnakeGame {
-            num_players,
-            target_fps,
-            is_four_key_steering,
+            settings,
+            saved_round: None,
         }
     }
 
-    pub fn run(self) -> Result<()> {
-        let event_queue = KeyEventQueue::new();
-        let thread_event_queue = event_queue.clone();
+    /// Continues a saved round, the following rounds start as usual.
+    pub fn resume(settings: GameSettings, saved_round: SavedRound) -> SnakeGame {
+        SnakeGame {
+            settings,
+            saved_round: Some(saved_round),
+        }
+    }
 
-        // launch seperate thread to deal with keyboard input
-        thread::spawn(move || send_events(&thread_event_queue));
+    pub fn run(mut self) -> Result<()> {
+        install_panic_hook();
+        // set on termination signals
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        register_stop_signals(&stop_requested)?;
 
-        let mut stdout = stdout();
-        enable_raw_mode()?;
-        stdout.execute(cursor::Hide)?;
+        let terminal_guard = TerminalGuard::new(&self.settings)?;
+        let mut input = InputEvents::new();
+        // the same generator as `StdRng`, but one whose state can be saved
+        let mut rng = ChaCha12Rng::from_entropy();
+        // decides which food is golden, separate from the other random events so that
+        // the daily challenge serves the same sequence of food however it is played
+        let mut food_kind_rng = ChaCha12Rng::from_entropy();
 
+        let mut stdout = stdout();
         stdout.execute(terminal::Clear(terminal::ClearType::All))?;
 
-        let screen_width = 40;
-        let screen_height = 40;
+        let screen_width = self.settings.arena_width;
+        let screen_height = self.settings.arena_height;
 
-        let mut screen_buffer = ScreenBuffer::new(screen_width, screen_height, GameContent::Empty);
+        let mut screen_buffer = ScreenBuffer::new(
+            screen_width,
+            screen_height,
+            GameContent::Empty,
+            self.settings.theme.clone(),
+            self.settings.render_mode,
+        );
 
         // clear screen
         screen_buffer.set_all(GameContent::Empty);
@@ -54,215 +344,846 @@ impl SnakeGame {
         screen_buffer.set_centered_text_at_row(screen_height / 2 - 4, "ESC to stop");
         screen_buffer.set_centered_text_at_row(screen_height / 2 + 2, "~ CONTROLS ~");
 
-        screen_buffer.set_centered_text_at_row(
-            screen_height / 2 + 4,
-            if self.is_four_key_steering {
-                "Player 1 (green): arrow keys"
-            } else {
-                "Player 1 (green): left and right arrow keys"
-            },
-        );
-
-        if self.num_players > 1 {
+        for (player_idx, controls) in PLAYER_CONTROLS
+            .iter()
+            .take(self.settings.num_players)
+            .enumerate()
+        {
             screen_buffer.set_centered_text_at_row(
-                screen_height / 2 + 6,
-                if self.is_four_key_steering {
-                    "Player 2 (yellow): W A S D keys"
-                } else {
-                    "Player 2 (yellow): A and D keys"
-                },
+                screen_height / 2 + 4 + 2 * player_idx,
+                &format!(
+                    "Player {} ({}): {}",
+                    player_idx + 1,
+                    self.settings.theme.team_color_names[self.settings.team_of_player(player_idx)],
+                    self.controls_help(player_idx, controls)
+                ),
             );
         }
 
-        for n in (0..5).rev() {
-            screen_buffer
-                .set_centered_text_at_row(screen_height - 2, &format!("Starting in {}", n));
+        let mut must_exit = false;
+        let countdown = Duration::from_secs(5);
+        let countdown_begin = Instant::now();
+        loop {
+            let elapsed = countdown_begin.elapsed();
+            if elapsed >= countdown {
+                break;
+            }
+            let remaining = countdown.saturating_sub(elapsed);
+            screen_buffer.set_centered_text_at_row(
+                screen_height - 2,
+                &format!("Starting in {}", remaining.as_secs()),
+            );
+            screen_buffer.set_centered_text_at_row(
+                screen_height - 4,
+                &countdown_bar(remaining, countdown, 20),
+            );
             screen_buffer.draw(&mut stdout)?;
-            thread::sleep(Duration::from_secs(1));
+
+            input.wait_until(Instant::now() + ANIMATION_FRAME_TIME)?;
+            let events = input.take_all();
+            if self.actions(&events).contains(&Action::Exit)
+                || stop_requested.load(Ordering::Relaxed)
+            {
+                must_exit = true;
+                break;
+            }
+            if events.contains(&GameEvent::Resize) {
+                stdout.execute(terminal::Clear(terminal::ClearType::All))?;
+                screen_buffer.invalidate();
+            }
         }
 
-        let mut must_exit = false;
+        // best score of any team in this session
+        let mut high_score = 0;
+        let mut score_tables = ScoreTables::load();
+        // printed once the terminal is restored, e.g. the result of the official attempt
+        // at the daily challenge
+        let mut exit_message = None;
+        let mut saved_round = self.saved_round.take();
         while !must_exit {
-            let mut players = vec![Player::new(
-                KeyEvent::from(KeyCode::Left),
-                KeyEvent::from(KeyCode::Right),
-                KeyEvent::from(KeyCode::Up),
-                KeyEvent::from(KeyCode::Down),
-                0,
-            )];
-            if self.num_players == 2 {
-                players.push(Player::new(
-                    KeyEvent::from(KeyCode::Char('a')),
-                    KeyEvent::from(KeyCode::Char('d')),
-                    KeyEvent::from(KeyCode::Char('w')),
-                    KeyEvent::from(KeyCode::Char('s')),
-                    1,
-                ));
+            if let Some(daily) = &self.settings.daily {
+                rng = ChaCha12Rng::seed_from_u64(daily.seed);
+                food_kind_rng = ChaCha12Rng::seed_from_u64(!daily.seed);
             }
+            let shrink_interval_ticks = match self.settings.game_mode {
+                GameMode::BattleRoyale(interval) => Some(
+                    ((interval.as_secs_f64() * self.settings.target_fps).round() as u64).max(1),
+                ),
+                _ => None,
+            };
+            // the save file stays until the resumed round has started, so that the game
+            // isn't lost if it is stopped during the countdown
+            let mut must_remove_save = saved_round.is_some();
+            // a saved round continues where it was left, `played_before` is the time it had
+            // been played before it was saved
+            let (mut players, mut arena, mut tick, played_before) = match saved_round.take() {
+                Some(round) => {
+                    rng = round.rng;
+                    food_kind_rng = round.food_kind_rng;
+                    high_score = round.high_score;
+                    (round.players, round.arena, round.tick, round.elapsed)
+                }
+                None => {
+                    let players: Vec<Player> = (0..self.settings.num_players)
+                        .map(|player_idx| {
+                            Player::new(
+                                player_idx,
+                                self.settings.team_of_player(player_idx),
+                                self.settings.steering[player_idx],
+                                Snake::new(player_idx, screen_width, screen_height),
+                            )
+                        })
+                        .collect();
 
-            screen_buffer.set_all(GameContent::Empty);
+                    let mut arena = match &self.settings.level {
+                        Some(level) => Arena::from_level(level),
+                        None => Arena::new(screen_width, screen_height),
+                    };
+                    let (snake_cells, heads) = alive_snake_cells(&players);
+                    arena.place_random_portals(
+                        self.settings.random_portals,
+                        &snake_cells,
+                        &mut rng,
+                    );
+                    arena.place_random_patrols(
+                        self.settings.random_patrols,
+                        &snake_cells,
+                        &heads,
+                        &mut rng,
+                    );
+                    arena.next_shrink_tick = shrink_interval_ticks;
+                    (players, arena, 0, Duration::ZERO)
+                }
+            };
 
-            let mut food_pos = Coordinate { row: 10, col: 15 };
+            screen_buffer.set_all(GameContent::Empty);
+            let mut animations = Animations::new();
+            self.replenish_food(
+                &mut arena,
+                &players,
+                tick,
+                &mut rng,
+                &mut food_kind_rng,
+                &mut animations,
+            );
 
-            // 0: up, 1: right, 2: down, 3: left
-            let mut game_loop_begin = std::time::SystemTime::now();
-            let mut game_loop_end = std::time::SystemTime::now();
-            let horizontal_target_cycle_time = Duration::from_secs_f64(1.0 / self.target_fps);
-            let mut score = 0;
+            let tick_duration = Duration::from_secs_f64(1.0 / self.settings.target_fps);
+            let mut round_begin = Instant::now();
+            let mut next_tick = round_begin + tick_duration;
+            let mut next_frame = round_begin;
+            let mut paused_since: Option<Instant> = None;
+            // time played in this round, without pauses
+            let mut elapsed = played_before;
+            let mut round_end = RoundEnd::Crash;
             'outer: loop {
-                // ensure constant cycle time of game loop (i.e. constant snake speed)
-                let game_loop_runtime = game_loop_end.duration_since(game_loop_begin).unwrap();
-                let target_cycle_time = horizontal_target_cycle_time;
-
-                if game_loop_runtime < target_cycle_time {
-                    thread::sleep(target_cycle_time - game_loop_runtime);
+                if stop_requested.load(Ordering::Relaxed) {
+                    must_exit = true;
+                    break 'outer;
                 }
 
-                game_loop_begin = std::time::SystemTime::now();
-                if let Some(events) = event_queue.get_all_events() {
-                    if !events.is_empty() {
-                        if !find_matches(
-                            &events,
-                            &[
-                                KeyEvent::from(KeyCode::Esc),
-                                KeyEvent::from(KeyCode::Char('q')),
-                            ],
-                        )
-                        .is_empty()
-                        {
-                            must_exit = true;
-                            break 'outer;
+                let now = Instant::now();
+                let events = input.take_all();
+                let actions = self.actions(&events);
+                if actions.contains(&Action::Exit) {
+                    must_exit = true;
+                    break 'outer;
+                }
+                // the daily challenge can't be saved, it has one attempt only
+                if actions.contains(&Action::SaveAndExit) && self.settings.daily.is_none() {
+                    let round = SavedRound {
+                        players: players.clone(),
+                        arena: arena.clone(),
+                        tick,
+                        elapsed: played_before
+                            + paused_since.unwrap_or(now).duration_since(round_begin),
+                        high_score,
+                        rng: rng.clone(),
+                        food_kind_rng: food_kind_rng.clone(),
+                    };
+                    exit_message = Some(match save_game(&self.settings, &round) {
+                        Ok(path) => format!(
+                            "Saved the game to {}, continue it with --resume",
+                            path.display()
+                        ),
+                        Err(err) => format!("Could not save the game: {}", err),
+                    });
+                    must_exit = true;
+                    break 'outer;
+                }
+                for event in &events {
+                    match event {
+                        GameEvent::Resize => {
+                            stdout.execute(terminal::Clear(terminal::ClearType::All))?;
+                            screen_buffer.invalidate();
+                            next_frame = now;
                         }
-                        for player in &mut players {
-                            let event_matches = find_matches(
-                                &events,
-                                &[
-                                    player.left_key,
-                                    player.right_key,
-                                    player.up_key,
-                                    player.down_key,
-                                ],
-                            );
-
-                            if !event_matches.is_empty() {
-                                player.update_snake_direction(
-                                    *event_matches.last().unwrap(),
-                                    self.is_four_key_steering,
-                                );
+                        GameEvent::FocusLost => {
+                            if paused_since.is_none() {
+                                paused_since = Some(now);
+                                next_frame = now;
                             }
                         }
+                        GameEvent::Pointer { column, line } => {
+                            if self.settings.is_mouse_steering && !players[0].has_crashed {
+                                players[0].steering_target = screen_buffer.cell_at(*column, *line);
+                            }
+                        }
+                        GameEvent::FocusGained | GameEvent::Key(_) => {
+                            if let Some(pause_begin) = paused_since.take() {
+                                // the pause doesn't count towards the time of the round
+                                round_begin += now - pause_begin;
+                                next_tick = now + tick_duration;
+                                next_frame = now;
+                            }
+                        }
+                        // handled as an action above
+                        GameEvent::Interrupt => {}
                     }
                 }
 
-                for player in &mut players {
-                    move_snake(&mut player.snake.body_pos, player.snake.direction);
+                // turns are queued per player and applied one per tick
+                for action in actions {
+                    if let Action::Steer { player_idx, key } = action {
+                        if !players[player_idx].has_crashed {
+                            players[player_idx].queue_key(key);
+                        }
+                    }
                 }
 
-                let mut food_found = false;
-                for player in &mut players {
-                    if player.snake.body_pos[0] == food_pos {
-                        score += 1;
-                        food_found = true;
-
-                        // grow snake
-                        for _i in 0..3 {
-                            player
-                                .snake
-                                .body_pos
-                                .push(*player.snake.body_pos.last().unwrap());
+                elapsed = played_before + paused_since.unwrap_or(now).duration_since(round_begin);
+                if paused_since.is_none() && now >= next_tick {
+                    // ticks are scheduled on a fixed grid, so slow frames don't make the game
+                    // drift; after a long stall (e.g. a suspended terminal) missed ticks are dropped
+                    next_tick += tick_duration;
+                    if now > next_tick + MAX_TICK_LAG {
+                        next_tick = now + tick_duration;
+                    }
+                    // show the result of the tick right away
+                    next_frame = now;
+                    tick += 1;
+
+                    if must_remove_save {
+                        must_remove_save = false;
+                        if let Err(err) = remove_saved_game() {
+                            exit_message =
+                                Some(format!("Could not remove the saved game: {}", err));
                         }
                     }
-                }
 
-                if food_found {
-                    loop {
-                        let new_food_pos = get_random_food_pos(screen_height, screen_width);
-                        let has_collision = players.iter().any(|player| {
-                            snake_item_collision(&player.snake.body_pos, &new_food_pos)
-                        });
+                    for player in players.iter_mut().filter(|p| !p.has_crashed) {
+                        player.apply_queued_key();
+                        player.steer_towards_target();
+                    }
+
+                    let (snake_cells, heads) = alive_snake_cells(&players);
+                    arena.update_hazards(
+                        tick,
+                        &snake_cells,
+                        &heads,
+                        self.settings.mine_count,
+                        &mut rng,
+                    );
+
+                    for player in players.iter_mut().filter(|p| !p.has_crashed) {
+                        move_snake(&mut player.snake.body_pos, player.snake.direction, &arena);
+                    }
+
+                    // snakes caught by the advancing border crash below
+                    if arena.next_shrink_tick == Some(tick) {
+                        arena.shrink();
+                        arena.next_shrink_tick = shrink_interval_ticks.map(|ticks| tick + ticks);
+                    }
+
+                    for player in players.iter_mut().filter(|p| !p.has_crashed) {
+                        let head = player.snake.body_pos[0];
+                        // growth and points of what the snake ate
+                        let (growth, points) = if let Some(food_idx) =
+                            arena.food.iter().position(|food| food.pos == head)
+                        {
+                            let kind = arena.food.remove(food_idx).kind;
+                            (kind.growth(self.settings.base_growth), kind.points())
+                        } else if let Some(wreck_idx) =
+                            arena.wreck_food.iter().position(|&f| f == head)
+                        {
+                            arena.wreck_food.remove(wreck_idx);
+                            (1, 1)
+                        } else {
+                            (0, 0)
+                        };
+
+                        if points > 0 {
+                            player.score += self.settings.scoring.score(
+                                points,
+                                self.settings.target_fps,
+                                player.snake.body_pos.len(),
+                            );
+
+                            // grow snake
+                            for _i in 0..growth {
+                                player
+                                    .snake
+                                    .body_pos
+                                    .push(*player.snake.body_pos.last().unwrap());
+                            }
+                        }
+                    }
 
-                        if !has_collision {
-                            food_pos = new_food_pos;
-                            break;
+                    if let GameMode::TargetLength(target_length) = self.settings.game_mode {
+                        if players.iter().any(|player| {
+                            !player.has_crashed && player.snake.body_pos.len() >= target_length
+                        }) {
+                            round_end = RoundEnd::TargetReached;
+                            break 'outer;
                         }
                     }
-                }
 
-                // check for snake border and snake ego collisions
-                for player in &mut players {
-                    if check_border_and_ego_collision(
-                        &player.snake.body_pos,
-                        screen_width,
-                        screen_height,
-                    ) {
-                        player.has_crashed = true;
+                    // food nobody ate in time moves somewhere else
+                    arena.food.retain(|food| {
+                        food.expires_at_tick
+                            .is_none_or(|expires_at_tick| expires_at_tick > tick)
+                    });
+                    self.replenish_food(
+                        &mut arena,
+                        &players,
+                        tick,
+                        &mut rng,
+                        &mut food_kind_rng,
+                        &mut animations,
+                    );
+                    // in a shrinking arena, the border ends the round once there is no space left
+                    if arena.food.is_empty() && arena.next_shrink_tick.is_none() {
+                        round_end = RoundEnd::BoardFull;
                         break 'outer;
                     }
-                }
 
-                if self.num_players == 2 {
-                    let collider = snake_snake_collision(
-                        &players[0].snake.body_pos,
-                        &players[1].snake.body_pos,
+                    // evaluate all collisions of this tick together, so that simultaneous crashes
+                    // (e.g. head-on collisions) are treated the same for every player
+                    let crashed_players = find_crashed_players(
+                        &players,
+                        &arena,
+                        tick,
+                        self.settings.pass_through_teammates,
                     );
-                    if collider >= 0 {
-                        players[collider as usize].has_crashed = true;
+                    for &player_idx in &crashed_players {
+                        players[player_idx].has_crashed = true;
+                        animations.crash(
+                            &players[player_idx].snake.body_pos,
+                            players[player_idx].team_idx,
+                        );
+                    }
+                    if self.settings.game_mode == GameMode::Classic && !crashed_players.is_empty() {
+                        break 'outer;
+                    }
+                    for &player_idx in &crashed_players {
+                        let body = &players[player_idx].snake.body_pos;
+                        match self.settings.wreckage {
+                            Wreckage::Vanish => {}
+                            // portals and hazards stay clear
+                            Wreckage::Obstacle => {
+                                for pos in body {
+                                    if arena.is_inside(pos)
+                                        && !arena.is_portal_at(pos)
+                                        && !arena.is_hazard_at(pos)
+                                    {
+                                        arena.obstacles.push(*pos);
+                                    }
+                                }
+                            }
+                            Wreckage::Food => {
+                                for pos in body {
+                                    if arena.is_inside(pos)
+                                        && !arena.is_portal_at(pos)
+                                        && !arena.is_hazard_at(pos)
+                                        && !arena.is_food_at(pos)
+                                    {
+                                        arena.wreck_food.push(*pos);
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    let num_alive_teams = count_teams(players.iter().filter(|p| !p.has_crashed));
+                    if num_alive_teams == 0
+                        || (count_teams(players.iter()) > 1 && num_alive_teams == 1)
+                    {
                         break 'outer;
                     }
                 }
 
-                // clear, update and draw screen buffer
-                screen_buffer.set_all(GameContent::Empty);
-                for (player_id, player) in players.iter().enumerate() {
-                    add_snake_to_buffer(&mut screen_buffer, &player.snake.body_pos, player_id);
+                let mut time_left = None;
+                if let GameMode::Timed(time_limit) | GameMode::TimeAttack(time_limit) =
+                    self.settings.game_mode
+                {
+                    if elapsed >= time_limit {
+                        round_end = RoundEnd::TimeUp;
+                        break 'outer;
+                    }
+                    time_left = Some(time_limit - elapsed);
+                }
+                let shrink_in = arena.next_shrink_tick.map(|shrink_tick| {
+                    Duration::from_secs_f64(
+                        shrink_tick.saturating_sub(tick) as f64 / self.settings.target_fps,
+                    )
+                });
+
+                if now >= next_frame {
+                    // without animations, nothing changes on screen until the next tick
+                    next_frame = if animations.is_running() {
+                        now + ANIMATION_FRAME_TIME
+                    } else if paused_since.is_some() {
+                        now + PAUSED_REDRAW_INTERVAL
+                    } else {
+                        next_tick
+                    };
+
+                    // clear, update and draw screen buffer
+                    add_arena_to_buffer(&mut screen_buffer, &players, &arena, tick);
+
+                    let scores = format_scores(&players, self.settings.teams.is_some());
+                    let clock = match (self.settings.game_mode, time_left, shrink_in) {
+                        (_, Some(time_left), _) => {
+                            Some(format!("Time: {}s", time_left.as_secs() + 1))
+                        }
+                        (_, None, Some(shrink_in)) => {
+                            Some(format!("Shrinking in: {}s", shrink_in.as_secs() + 1))
+                        }
+                        (GameMode::TargetLength(_), None, None) => {
+                            Some(format!("Time: {:.1}s", elapsed.as_secs_f64()))
+                        }
+                        _ => None,
+                    };
+                    if let Some(clock) = clock {
+                        screen_buffer.set_text_at_row(0, &clock, TextAlignment::Right);
+                        screen_buffer.set_text_at_row(
+                            0,
+                            &format!("Score: {}", scores),
+                            TextAlignment::Left,
+                        );
+                    } else {
+                        screen_buffer.set_centered_text_at_row(0, &format!("Score: {}", scores));
+                    }
+
+                    if self.settings.show_side_panel {
+                        screen_buffer.set_side_panel(self.side_panel(
+                            &players,
+                            elapsed,
+                            time_left,
+                            shrink_in,
+                            &self.best_result(high_score, &score_tables),
+                        ));
+                    }
+
+                    if paused_since.is_some() {
+                        screen_buffer.set_centered_text_at_row(screen_height / 2, "PAUSED");
+                        screen_buffer.set_centered_text_at_row(
+                            screen_height / 2 + 2,
+                            "press any key to continue",
+                        );
+                    }
+
+                    animations.draw_frame(&mut screen_buffer, &mut stdout)?;
                 }
-                screen_buffer.set_at(food_pos.row, food_pos.col, GameContent::Food);
-                screen_buffer.add_border(GameContent::Border);
 
-                screen_buffer.set_centered_text_at_row(0, &format!("Score: {}", score));
+                let wake_up = if paused_since.is_some() {
+                    next_frame
+                } else {
+                    next_tick.min(next_frame)
+                };
+                input.wait_until(wake_up)?;
+            }
 
-                screen_buffer.draw(&mut stdout)?;
+            // the round counts as played to the end even if the player stops the game
+            // while the crashed snakes are still flashing
+            let is_round_over = !must_exit;
+            if is_round_over {
+                // let the crashed snakes flash before showing the results
+                add_arena_to_buffer(&mut screen_buffer, &players, &arena, tick);
+                while animations.is_running() {
+                    animations.draw_frame(&mut screen_buffer, &mut stdout)?;
 
-                game_loop_end = std::time::SystemTime::now();
+                    input.wait_until(Instant::now() + ANIMATION_FRAME_TIME)?;
+                    let events = input.take_all();
+                    if self.actions(&events).contains(&Action::Exit)
+                        || stop_requested.load(Ordering::Relaxed)
+                    {
+                        must_exit = true;
+                        break;
+                    }
+                    if events.contains(&GameEvent::Resize) {
+                        stdout.execute(terminal::Clear(terminal::ClearType::All))?;
+                        screen_buffer.invalidate();
+                    }
+                }
             }
 
+            high_score = players
+                .iter()
+                .map(|player| team_score(&players, player.team_idx))
+                .fold(high_score, usize::max);
+
             // draw empty buffer
             screen_buffer.set_all(GameContent::Empty);
             screen_buffer.draw(&mut stdout)?;
 
             screen_buffer.set_centered_text_at_row(screen_height / 2 - 8, "! GAME OVER !");
 
+            let mut report_row = screen_height / 2 - 6;
+            for message in crash_report(
+                &players,
+                self.settings.game_mode,
+                round_end,
+                self.settings.teams.is_some(),
+            ) {
+                report_row += 1 + screen_buffer.set_wrapped_text_at_row(
+                    report_row,
+                    &message,
+                    TextAlignment::Center,
+                );
+            }
+
             screen_buffer.set_centered_text_at_row(
-                screen_height / 2 - 2,
-                &format!("Final Score: {}", score),
+                screen_height / 2 + 2,
+                &format!(
+                    "Final Score: {}",
+                    format_scores(&players, self.settings.teams.is_some())
+                ),
             );
 
+            let mut record_row = screen_height / 2 + 4;
+            // an attempt at the daily challenge counts even if it was cut short
+            if is_round_over || self.settings.daily.is_some() {
+                let messages = if self.settings.daily.is_some() {
+                    let (messages, summary) =
+                        self.record_daily_result(&players, round_end, elapsed, &mut score_tables);
+                    exit_message = summary.or(exit_message);
+                    messages
+                } else {
+                    self.record_result(&players, round_end, elapsed, &mut score_tables)
+                };
+                for message in messages {
+                    record_row += 1 + screen_buffer.set_wrapped_text_at_row(
+                        record_row,
+                        &message,
+                        TextAlignment::Center,
+                    );
+                }
+            }
+
             if !must_exit {
-                for n in (0..40).rev() {
+                // the countdown goes below the records and stays clear of "ESC to abort",
+                // also in half block mode; the bar is left out if there is no room for it
+                let abort_row = screen_height - 4;
+                let countdown_row = (screen_height / 2 + 10).max(record_row).min(abort_row - 2);
+                let bar_row = Some(countdown_row + 2).filter(|&row| row + 2 <= abort_row);
+                let countdown = Duration::from_secs(4);
+                let countdown_begin = Instant::now();
+                loop {
+                    let elapsed = countdown_begin.elapsed();
+                    if elapsed >= countdown {
+                        break;
+                    }
+                    let remaining = countdown.saturating_sub(elapsed);
                     screen_buffer.set_centered_text_at_row(
-                        screen_height / 2 + 10,
-                        &format!("Restarting in ... {}s", n / 10),
+                        countdown_row,
+                        &format!("Restarting in ... {}s", remaining.as_secs()),
                     );
-                    screen_buffer.set_centered_text_at_row(screen_height - 4, "ESC to abort");
+                    if let Some(bar_row) = bar_row {
+                        screen_buffer.set_centered_text_at_row(
+                            bar_row,
+                            &countdown_bar(remaining, countdown, 20),
+                        );
+                    }
+                    screen_buffer.set_centered_text_at_row(abort_row, "ESC to abort");
                     screen_buffer.draw(&mut stdout)?;
-                    if let Some(event) = event_queue.get_latest_event() {
-                        if event == KeyEvent::from(KeyCode::Esc)
-                            || event == KeyEvent::from(KeyCode::Char('q'))
-                        {
-                            must_exit = true;
-                            break;
-                        }
+
+                    input.wait_until(Instant::now() + RESTART_COUNTDOWN_STEP)?;
+                    let events = input.take_all();
+                    if self.actions(&events).contains(&Action::Exit)
+                        || stop_requested.load(Ordering::Relaxed)
+                    {
+                        must_exit = true;
+                        break;
+                    }
+                    if events.contains(&GameEvent::Resize) {
+                        stdout.execute(terminal::Clear(terminal::ClearType::All))?;
+                        screen_buffer.invalidate();
                     }
-                    thread::sleep(Duration::from_secs_f32(0.1));
                 }
             }
         }
-        stdout.execute(cursor::Show)?;
-        disable_raw_mode()
+
+        // restore the terminal first, so that the message stays visible
+        drop(terminal_guard);
+        if let Some(message) = exit_message {
+            println!("{}", message);
+        }
+        Ok(())
+    }
+
+    /// Adds food until the configured number of pieces lies in the arena, as far as
+    /// there is space left.
+    fn replenish_food<R: Rng>(
+        &self,
+        arena: &mut Arena,
+        players: &[Player],
+        tick: u64,
+        rng: &mut R,
+        food_kind_rng: &mut R,
+        animations: &mut Animations,
+    ) {
+        let (snake_cells, heads) = alive_snake_cells(players);
+        let expires_at_tick = self
+            .settings
+            .food_lifetime
+            .map(|lifetime| tick + (lifetime.as_secs_f64() * self.settings.target_fps) as u64);
+
+        while arena.food.len() < self.settings.food_count {
+            let kind = if food_kind_rng.gen_bool(self.settings.golden_food_chance) {
+                FoodKind::Golden
+            } else {
+                FoodKind::Regular
+            };
+            match arena.spawn_food(
+                kind,
+                self.settings.food_spawn,
+                &snake_cells,
+                &heads,
+                expires_at_tick,
+                rng,
+            ) {
+                Some(pos) => animations.blink(pos, kind.content()),
+                None => break,
+            }
+        }
+    }
+
+    /// Describes the best result so far: of all sessions in the challenge modes,
+    /// of the current session otherwise.
+    fn best_result(&self, high_score: usize, score_tables: &ScoreTables) -> String {
+        let game_mode = self.settings.game_mode;
+        if let Some(daily) = &self.settings.daily {
+            return format!(
+                "Official    {}",
+                score_tables
+                    .best(&daily.score_table(), Ranking::HighestFirst)
+                    .map_or("-".to_string(), |official| official.to_string())
+            );
+        }
+        match game_mode.score_table() {
+            Some((table, ranking)) => format!(
+                "Best        {}",
+                score_tables
+                    .best(&table, ranking)
+                    .map_or("-".to_string(), |best| game_mode.format_record(best))
+            ),
+            None => format!("High score  {}", high_score),
+        }
+    }
+
+    /// Records the result of a finished challenge round in its high score table and
+    /// describes it, together with the best results so far.
+    fn record_result(
+        &self,
+        players: &[Player],
+        round_end: RoundEnd,
+        elapsed: Duration,
+        score_tables: &mut ScoreTables,
+    ) -> Vec<String> {
+        let game_mode = self.settings.game_mode;
+        let (table, ranking) = match game_mode.score_table() {
+            Some(score_table) => score_table,
+            None => return vec![],
+        };
+        let result = match (game_mode, round_end) {
+            (GameMode::TargetLength(_), RoundEnd::TargetReached) => {
+                Some(elapsed.as_millis() as u64)
+            }
+            (GameMode::TargetLength(_), _) => None,
+            _ => Some(players[0].score as u64).filter(|&score| score > 0),
+        };
+
+        let mut messages = vec![];
+        if let Some(result) = result {
+            let result_text = game_mode.format_record(result);
+            messages.push(match score_tables.record(&table, result, ranking) {
+                Ok(Some(0)) => format!("New record: {}!", result_text),
+                Ok(Some(rank)) => format!("{} is number {} of your best", result_text, rank + 1),
+                Ok(None) => result_text,
+                Err(err) => format!("Could not save the high score: {}", err),
+            });
+        }
+        let best: Vec<String> = score_tables
+            .table(&table, ranking)
+            .iter()
+            .take(3)
+            .enumerate()
+            .map(|(idx, entry)| format!("{}. {}", idx + 1, game_mode.format_record(entry.value)))
+            .collect();
+        if !best.is_empty() {
+            messages.push(format!("Best: {}", best.join("  ")));
+        }
+        messages
+    }
+
+    /// Records the first attempt of the day at the daily challenge as the official one,
+    /// later ones are for practice. Returns what to show and, for the official attempt,
+    /// the summary to share.
+    fn record_daily_result(
+        &self,
+        players: &[Player],
+        round_end: RoundEnd,
+        elapsed: Duration,
+        score_tables: &mut ScoreTables,
+    ) -> (Vec<String>, Option<String>) {
+        let daily = match &self.settings.daily {
+            Some(daily) => daily,
+            None => return (vec![], None),
+        };
+        let table = daily.score_table();
+        if let Some(official) = score_tables.best(&table, Ranking::HighestFirst) {
+            return (
+                vec![format!(
+                    "Practice round, today's official score is {}",
+                    official
+                )],
+                None,
+            );
+        }
+
+        let player = &players[0];
+        let secs = elapsed.as_secs();
+        let ending = match round_end {
+            RoundEnd::TimeUp => format!("survived {}s", secs),
+            RoundEnd::BoardFull => format!("filled the board in {}s", secs),
+            _ if player.has_crashed => format!("crashed after {}s", secs),
+            _ => format!("gave up after {}s", secs),
+        };
+        let summary = daily.summary(player.score, player.snake.body_pos.len(), &ending);
+        let recorded = match score_tables.record(&table, player.score as u64, Ranking::HighestFirst)
+        {
+            Ok(_) => format!("Official score: {}", player.score),
+            Err(err) => format!("Could not save the official score: {}", err),
+        };
+        (
+            vec![
+                recorded,
+                summary.clone(),
+                "This summary is printed again when you quit".to_string(),
+            ],
+            Some(summary),
+        )
+    }
+
+    /// Translates the key presses among the events into what they mean for the game.
+    fn actions(&self, events: &[GameEvent]) -> Vec<Action> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::Key(KeyCode::Esc)
+                | GameEvent::Key(KeyCode::Char('q'))
+                | GameEvent::Interrupt => Some(Action::Exit),
+                GameEvent::Key(SAVE_KEY) => Some(Action::SaveAndExit),
+                GameEvent::Key(key_code) => PLAYER_CONTROLS
+                    .iter()
+                    .take(self.settings.num_players)
+                    .enumerate()
+                    .find_map(|(player_idx, controls)| {
+                        controls
+                            .steering_key(*key_code)
+                            .map(|key| Action::Steer { player_idx, key })
+                    }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn controls_help(&self, player_idx: usize, controls: &PlayerControls) -> String {
+        let keys_help = match self.settings.steering[player_idx] {
+            SteeringMode::FourKeys => controls.four_key_help.to_string(),
+            SteeringMode::TwoKeys => controls.two_key_help.to_string(),
+            SteeringMode::Hybrid => {
+                format!("{} + {}", controls.four_key_help, controls.turn_help)
+            }
+        };
+        if self.settings.is_mouse_steering && player_idx == 0 {
+            format!("{} or mouse", keys_help)
+        } else {
+            keys_help
+        }
+    }
+
+    fn side_panel(
+        &self,
+        players: &[Player],
+        elapsed: Duration,
+        time_left: Option<Duration>,
+        shrink_in: Option<Duration>,
+        best_result: &str,
+    ) -> Vec<PanelLine> {
+        let format_duration = |duration: Duration| {
+            format!("{}:{:02}", duration.as_secs() / 60, duration.as_secs() % 60)
+        };
+
+        let mut lines = vec![PanelLine::new(format!(
+            "Time        {}",
+            format_duration(elapsed)
+        ))];
+        if let Some(time_left) = time_left {
+            lines.push(PanelLine::new(format!(
+                "Time left   {}",
+                format_duration(time_left)
+            )));
+        }
+        if let Some(shrink_in) = shrink_in {
+            lines.push(PanelLine::new(format!(
+                "Shrinking   {}",
+                format_duration(shrink_in)
+            )));
+        }
+        lines.push(PanelLine::new(format!(
+            "Speed       {:.1} moves/s",
+            self.settings.target_fps
+        )));
+        lines.push(PanelLine::new(best_result.to_string()));
+
+        for (player, controls) in players.iter().zip(PLAYER_CONTROLS.iter()) {
+            lines.push(PanelLine::new(String::new()));
+            lines.push(PanelLine::for_team(
+                format!(
+                    "Player {} ({})",
+                    player.player_idx + 1,
+                    self.settings.theme.team_color_names[player.team_idx]
+                ),
+                player.team_idx,
+            ));
+            if player.has_crashed {
+                lines.push(PanelLine::new("  crashed".to_string()));
+            } else {
+                lines.push(PanelLine::new(format!(
+                    "  Length {}",
+                    player.snake.body_pos.len()
+                )));
+            }
+            lines.push(PanelLine::new(format!("  Score  {}", player.score)));
+            lines.push(PanelLine::new(format!(
+                "  {}",
+                self.controls_help(player.player_idx, controls)
+            )));
+        }
+
+        lines.push(PanelLine::new(String::new()));
+        lines.push(PanelLine::new("ESC or q to stop".to_string()));
+        if self.settings.daily.is_none() {
+            lines.push(PanelLine::new("F2 to save and stop".to_string()));
+        }
+        lines
     }
 }
 
-pub fn move_snake(snake: &mut Vec<Coordinate>, snake_direction: Direction) {
+/// Moves the snake one step ahead. A head entering a portal comes out of its other end,
+/// so neighbouring segments are not necessarily adjacent cells.
+pub fn move_snake(snake: &mut Vec<Coordinate>, snake_direction: Direction, arena: &Arena) {
     // add head in new direction
     let new_head = match snake_direction {
         Direction::Up => Coordinate {
@@ -286,57 +1207,204 @@ pub fn move_snake(snake: &mut Vec<Coordinate>, snake_direction: Direction) {
             col: snake[0].col - 1,
         },
     };
+    let new_head = arena.portal_exit(&new_head).unwrap_or(new_head);
 
     snake.insert(0, new_head);
     // remove tail
     snake.pop();
 }
 
+/// Returns all cells taken by the snakes still in the game, and their heads.
+pub fn alive_snake_cells(players: &[Player]) -> (Vec<Coordinate>, Vec<Coordinate>) {
+    let alive_players = players.iter().filter(|player| !player.has_crashed);
+    let snake_cells = alive_players
+        .clone()
+        .flat_map(|player| player.snake.body_pos.iter().copied())
+        .collect();
+    let heads = alive_players
+        .map(|player| player.snake.body_pos[0])
+        .collect();
+    (snake_cells, heads)
+}
+
 pub fn snake_item_collision(snake: &[Coordinate], item: &Coordinate) -> bool {
     let is_collision = snake.iter().position(|&r| r == *item);
     is_collision.is_some()
 }
 
-pub fn check_border_and_ego_collision(
-    snake_body: &[Coordinate],
-    screen_width: usize,
-    screen_height: usize,
-) -> bool {
-    snake_body[0].row == 0
-        || snake_body[0].row == screen_height - 1
-        || snake_body[0].col == 0
-        || snake_body[0].col == screen_width - 1
+/// Returns whether the snake hit the border or itself. A border advancing by shrinking
+/// catches the snake anywhere along its body.
+pub fn check_border_and_ego_collision(snake_body: &[Coordinate], arena: &Arena) -> bool {
+    snake_body.iter().any(|pos| !arena.is_inside(pos))
         || snake_item_collision(&snake_body[1..], &snake_body[0])
 }
 
-pub fn snake_snake_collision(snake_a: &[Coordinate], snake_b: &[Coordinate]) -> i64 {
-    if snake_item_collision(&snake_a[1..], &snake_b[0]) {
-        1
-    } else if snake_item_collision(&snake_b[1..], &snake_a[0]) {
-        0
-    } else {
-        -1
-    }
+/// Returns whether snake a and snake b crashed into each other, in that order.
+/// Both snakes crash if their heads meet in the same cell or if they swap places.
+pub fn snake_snake_collision(snake_a: &[Coordinate], snake_b: &[Coordinate]) -> (bool, bool) {
+    let is_head_on = snake_a[0] == snake_b[0];
+    let a_crashed = is_head_on || snake_item_collision(&snake_b[1..], &snake_a[0]);
+    let b_crashed = is_head_on || snake_item_collision(&snake_a[1..], &snake_b[0]);
+    (a_crashed, b_crashed)
 }
 
-pub fn get_random_food_pos(screen_height: usize, screen_width: usize) -> Coordinate {
-    let mut rng = rand::thread_rng();
-    // screen width and height -2, since -1 is the index of the border
-    let row = rng.gen_range(1..(screen_height - 2));
-    let col = rng.gen_range(1..(screen_width - 2));
-    Coordinate { row, col }
-}
+/// Returns the indices of all players that crashed in the current tick.
+/// Players that have crashed before are no longer part of the arena and are skipped.
+pub fn find_crashed_players(
+    players: &[Player],
+    arena: &Arena,
+    tick: u64,
+    pass_through_teammates: bool,
+) -> Vec<usize> {
+    let mut has_crashed: Vec<bool> = players
+        .iter()
+        .map(|player| {
+            !player.has_crashed
+                && (check_border_and_ego_collision(&player.snake.body_pos, arena)
+                    || arena.is_deadly_at(&player.snake.body_pos[0], tick))
+        })
+        .collect();
 
-pub fn find_matches<T: PartialEq + Copy>(look_in: &[T], look_for: &[T]) -> Vec<T> {
-    let mut found: Vec<T> = vec![];
-    for a in look_for {
-        for b in look_in {
-            if a == b {
-                found.push(*b);
+    for idx_a in 0..players.len() {
+        for idx_b in (idx_a + 1)..players.len() {
+            if players[idx_a].has_crashed || players[idx_b].has_crashed {
+                continue;
             }
+            if pass_through_teammates && players[idx_a].team_idx == players[idx_b].team_idx {
+                continue;
+            }
+            let (a_crashed, b_crashed) = snake_snake_collision(
+                &players[idx_a].snake.body_pos,
+                &players[idx_b].snake.body_pos,
+            );
+            has_crashed[idx_a] |= a_crashed;
+            has_crashed[idx_b] |= b_crashed;
         }
     }
-    found
+
+    has_crashed
+        .iter()
+        .enumerate()
+        .filter(|&(_, &crashed)| crashed)
+        .map(|(player_idx, _)| player_idx)
+        .collect()
+}
+
+/// Returns the number of distinct teams the given players belong to.
+pub fn count_teams<'a>(players: impl Iterator<Item = &'a Player>) -> usize {
+    let mut teams: Vec<usize> = players.map(|player| player.team_idx).collect();
+    teams.sort_unstable();
+    teams.dedup();
+    teams.len()
+}
+
+/// Teammates share their score.
+pub fn team_score(players: &[Player], team_idx: usize) -> usize {
+    players
+        .iter()
+        .filter(|player| player.team_idx == team_idx)
+        .map(|player| player.score)
+        .sum()
+}
+
+/// Formats the score of every team taking part, e.g. "3" or "P1:3 P2:5".
+pub fn format_scores(players: &[Player], has_teams: bool) -> String {
+    let mut teams: Vec<usize> = players.iter().map(|player| player.team_idx).collect();
+    teams.sort_unstable();
+    teams.dedup();
+
+    if let [team_idx] = teams.as_slice() {
+        return team_score(players, *team_idx).to_string();
+    }
+
+    teams
+        .iter()
+        .map(|&team_idx| {
+            format!(
+                "{}{}:{}",
+                if has_teams { "T" } else { "P" },
+                team_idx + 1,
+                team_score(players, team_idx)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Describes the outcome of a round, one line per message.
+/// Without teams, every player forms a team of their own.
+pub fn crash_report(
+    players: &[Player],
+    game_mode: GameMode,
+    round_end: RoundEnd,
+    has_teams: bool,
+) -> Vec<String> {
+    let mut messages = vec![];
+    match round_end {
+        RoundEnd::Crash => {}
+        RoundEnd::TimeUp => messages.push("Time is up!".to_string()),
+        RoundEnd::BoardFull => messages.push("The board is full, perfect game!".to_string()),
+        RoundEnd::TargetReached => messages.push("Target length reached!".to_string()),
+    }
+
+    let crashed: Vec<usize> = players
+        .iter()
+        .filter(|player| player.has_crashed)
+        .map(|player| player.player_idx + 1)
+        .collect();
+
+    if players.len() < 2 || (crashed.is_empty() && round_end == RoundEnd::Crash) {
+        return messages;
+    }
+
+    let mut survivors: Vec<&Player> = players.iter().filter(|p| !p.has_crashed).collect();
+    if game_mode == GameMode::Classic {
+        // in classic mode, a single crash costs the whole team the round
+        survivors.retain(|survivor| {
+            !players
+                .iter()
+                .any(|p| p.has_crashed && p.team_idx == survivor.team_idx)
+        });
+    }
+    if round_end != RoundEnd::Crash {
+        // the longest surviving snakes win when time runs out or the board is full
+        let max_len = survivors
+            .iter()
+            .map(|p| p.snake.body_pos.len())
+            .max()
+            .unwrap_or(0);
+        survivors.retain(|p| p.snake.body_pos.len() == max_len);
+    }
+
+    let mut winning_teams: Vec<usize> = survivors.iter().map(|p| p.team_idx).collect();
+    winning_teams.sort_unstable();
+    winning_teams.dedup();
+
+    match winning_teams.as_slice() {
+        [] => messages.push("DRAW!".to_string()),
+        [winner] => messages.push(format!(
+            "{} {} wins!",
+            if has_teams { "Team" } else { "Player" },
+            winner + 1
+        )),
+        _ if round_end != RoundEnd::Crash => messages.push("DRAW!".to_string()),
+        _ => {}
+    };
+
+    match crashed.as_slice() {
+        [] => {}
+        [single] => messages.push(format!("Player {} crashed", single)),
+        [init @ .., last] => messages.push(format!(
+            "Players {} and {} crashed",
+            init.iter()
+                .map(|idx| idx.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            last
+        )),
+    };
+
+    messages
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -347,20 +1415,13 @@ pub struct Snake {
 }
 
 impl Snake {
-    pub fn new(player_idx: usize) -> Snake {
+    pub fn new(player_idx: usize, screen_width: usize, screen_height: usize) -> Snake {
+        let col = screen_width / 4 + player_idx * 5;
+        let row = screen_height / 2 - 2;
         let snake_body = vec![
-            Coordinate {
-                row: 18,
-                col: 10 + player_idx * 5,
-            },
-            Coordinate {
-                row: 19,
-                col: 10 + player_idx * 5,
-            },
-            Coordinate {
-                row: 20,
-                col: 10 + player_idx * 5,
-            },
+            Coordinate { row, col },
+            Coordinate { row: row + 1, col },
+            Coordinate { row: row + 2, col },
         ];
         Snake {
             body_pos: snake_body,
@@ -377,64 +1438,132 @@ pub enum Direction {
     Right,
 }
 
+impl Direction {
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct Player {
-    pub left_key: crossterm::event::KeyEvent,
-    pub right_key: crossterm::event::KeyEvent,
-    pub up_key: crossterm::event::KeyEvent,
-    pub down_key: crossterm::event::KeyEvent,
     pub snake: Snake,
     pub player_idx: usize,
+    pub team_idx: usize,
+    pub steering: SteeringMode,
+    pub score: usize,
     pub has_crashed: bool,
+    /// steering keys pressed since the last tick, applied one per tick
+    pub queued_keys: VecDeque<SteeringKey>,
+    /// cell the snake heads for when steered with the mouse
+    pub steering_target: Option<Coordinate>,
 }
 
 impl Player {
-    pub fn new(
-        left_key: crossterm::event::KeyEvent,
-        right_key: crossterm::event::KeyEvent,
-        up_key: crossterm::event::KeyEvent,
-        down_key: crossterm::event::KeyEvent,
-        player_idx: usize,
-    ) -> Player {
+    pub fn new(player_idx: usize, team_idx: usize, steering: SteeringMode, snake: Snake) -> Player {
         Player {
-            snake: Snake::new(player_idx),
-            left_key,
-            right_key,
-            up_key,
-            down_key,
+            snake,
             player_idx,
+            team_idx,
+            steering,
+            score: 0,
             has_crashed: false,
+            queued_keys: VecDeque::new(),
+            steering_target: None,
         }
     }
-    pub fn update_snake_direction(
-        &mut self,
-        key_event: crossterm::event::KeyEvent,
-        is_four_key_steering: bool,
-    ) {
-        if is_four_key_steering {
-            self._update_direction_four_keys(key_event);
+
+    pub fn queue_key(&mut self, key: SteeringKey) {
+        // the keyboard takes over from the mouse
+        self.steering_target = None;
+        if self.queued_keys.len() < MAX_QUEUED_KEYS {
+            self.queued_keys.push_back(key);
+        }
+    }
+
+    pub fn apply_queued_key(&mut self) {
+        if let Some(key) = self.queued_keys.pop_front() {
+            self.update_snake_direction(key);
+        }
+    }
+
+    /// Turns towards the steering target, along the axis on which it is further away.
+    /// The snake never reverses, a target straight behind it makes it turn to the side.
+    pub fn steer_towards_target(&mut self) {
+        let target = match self.steering_target {
+            Some(target) => target,
+            None => return,
+        };
+        let head = self.snake.body_pos[0];
+        if head == target {
+            self.steering_target = None;
+            return;
+        }
+
+        let row_distance = target.row as i64 - head.row as i64;
+        let col_distance = target.col as i64 - head.col as i64;
+        let vertical = if row_distance < 0 {
+            Direction::Up
         } else {
-            self._update_direction_two_keys(key_event);
+            Direction::Down
+        };
+        let horizontal = if col_distance < 0 {
+            Direction::Left
+        } else {
+            Direction::Right
+        };
+        let mut candidates = if row_distance.abs() >= col_distance.abs() {
+            vec![(vertical, row_distance), (horizontal, col_distance)]
+        } else {
+            vec![(horizontal, col_distance), (vertical, row_distance)]
+        };
+        candidates.retain(|(direction, distance)| {
+            *distance != 0 && *direction != self.snake.direction.opposite()
+        });
+
+        self.snake.direction = match candidates.first() {
+            Some((direction, _)) => *direction,
+            None => match self.snake.direction {
+                Direction::Left | Direction::Right => Direction::Up,
+                Direction::Up | Direction::Down => Direction::Left,
+            },
+        };
+    }
+
+    pub fn update_snake_direction(&mut self, key: SteeringKey) {
+        match (self.steering, key) {
+            (SteeringMode::TwoKeys, _) => self._update_direction_two_keys(key),
+            (SteeringMode::Hybrid, SteeringKey::TurnLeft) => {
+                self._update_direction_two_keys(SteeringKey::Left)
+            }
+            (SteeringMode::Hybrid, SteeringKey::TurnRight) => {
+                self._update_direction_two_keys(SteeringKey::Right)
+            }
+            _ => self._update_direction_four_keys(key),
         }
     }
 
-    fn _update_direction_four_keys(&mut self, key_event: crossterm::event::KeyEvent) {
-        if key_event == self.up_key
+    fn _update_direction_four_keys(&mut self, key: SteeringKey) {
+        if key == SteeringKey::Up
             && self.snake.direction != Direction::Up
             && self.snake.direction != Direction::Down
         {
             self.snake.direction = Direction::Up;
-        } else if key_event == self.down_key
+        } else if key == SteeringKey::Down
             && self.snake.direction != Direction::Up
             && self.snake.direction != Direction::Down
         {
             self.snake.direction = Direction::Down;
-        } else if key_event == self.left_key
+        } else if key == SteeringKey::Left
             && self.snake.direction != Direction::Right
             && self.snake.direction != Direction::Left
         {
             self.snake.direction = Direction::Left;
-        } else if key_event == self.right_key
+        } else if key == SteeringKey::Right
             && self.snake.direction != Direction::Right
             && self.snake.direction != Direction::Left
         {
@@ -442,7 +1571,7 @@ impl Player {
         }
     }
 
-    fn _update_direction_two_keys(&mut self, key_event: crossterm::event::KeyEvent) {
+    fn _update_direction_two_keys(&mut self, key: SteeringKey) {
         let directions_ordered = [
             Direction::Up,
             Direction::Right,
@@ -454,9 +1583,9 @@ impl Player {
             .position(|&r| r == self.snake.direction)
             .unwrap() as i64;
 
-        if key_event == self.left_key {
+        if key == SteeringKey::Left {
             current_dir_index -= 1;
-        } else if key_event == self.right_key {
+        } else if key == SteeringKey::Right {
             current_dir_index += 1;
         }
 
@@ -469,26 +1598,232 @@ impl Player {
     }
 }
 
+/// Draws everything inside the arena except for text.
+pub fn add_arena_to_buffer(
+    screen_buffer: &mut ScreenBuffer,
+    players: &[Player],
+    arena: &Arena,
+    tick: u64,
+) {
+    screen_buffer.set_all(GameContent::Empty);
+    for pos in &arena.obstacles {
+        screen_buffer.set_at(pos.row, pos.col, GameContent::Obstacle);
+    }
+    for pos in &arena.wreck_food {
+        screen_buffer.set_at(pos.row, pos.col, GameContent::Food);
+    }
+    for (portal_idx, (first, second)) in arena.portals.iter().enumerate() {
+        screen_buffer.set_at(first.row, first.col, GameContent::Portal(portal_idx));
+        screen_buffer.set_at(second.row, second.col, GameContent::Portal(portal_idx));
+    }
+    for hazard in &arena.hazards {
+        if let Some(content) = hazard.content(tick) {
+            for pos in hazard.cells() {
+                screen_buffer.set_at(pos.row, pos.col, content);
+            }
+        }
+    }
+    for player in players.iter().filter(|p| !p.has_crashed) {
+        add_snake_to_buffer(screen_buffer, &player.snake.body_pos, player.team_idx);
+    }
+    for food in &arena.food {
+        screen_buffer.set_at(food.pos.row, food.pos.col, food.kind.content());
+    }
+    let mut border_thickness = arena.inset + 1;
+    // the next ring blinks before the arena shrinks
+    let is_shrinking_soon = arena
+        .next_shrink_tick
+        .is_some_and(|shrink_tick| shrink_tick.saturating_sub(tick) <= SHRINK_WARNING_TICKS);
+    if is_shrinking_soon && tick.is_multiple_of(2) {
+        border_thickness += 1;
+    }
+    screen_buffer.add_border(GameContent::Border, border_thickness);
+}
+
 pub fn add_snake_to_buffer(
     screen_buffer: &mut ScreenBuffer,
     snake: &[Coordinate],
-    player_idx: usize,
+    team_idx: usize,
 ) {
+    let links_of_segment = |idx: usize| {
+        let mut links = SegmentLinks::default();
+        if idx > 0 {
+            links.add_link(&snake[idx], &snake[idx - 1]);
+        }
+        if idx + 1 < snake.len() {
+            links.add_link(&snake[idx], &snake[idx + 1]);
+        }
+        links
+    };
+
     screen_buffer.set_at(
         snake[0].row,
         snake[0].col,
-        GameContent::SnakeHead(player_idx),
+        GameContent::SnakeHead(team_idx, links_of_segment(0)),
     );
 
-    // only use rest of the body
-    let snake_body: Vec<&Coordinate> = snake
-        .iter()
-        .enumerate()
-        .filter(|&(i, _)| i != 0)
-        .map(|(_, v)| v)
-        .collect();
+    // only use rest of the body, skipping segments stacked up by growing
+    for idx in 1..snake.len() {
+        if snake[idx] == snake[idx - 1] {
+            continue;
+        }
+        screen_buffer.set_at(
+            snake[idx].row,
+            snake[idx].col,
+            GameContent::SnakeBody(team_idx, links_of_segment(idx)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CollisionCase {
+        name: &'static str,
+        /// body of each snake from head to tail, and the direction it moves in
+        snake_a: (&'static [(usize, usize)], Direction),
+        snake_b: (&'static [(usize, usize)], Direction),
+        same_team: bool,
+        /// whether snake a and snake b crash
+        expected: (bool, bool),
+    }
+
+    const COLLISION_CASES: [CollisionCase; 6] = [
+        CollisionCase {
+            name: "heads meet in the same cell",
+            snake_a: (&[(10, 5), (10, 4), (10, 3)], Direction::Right),
+            snake_b: (&[(10, 7), (10, 8), (10, 9)], Direction::Left),
+            same_team: false,
+            expected: (true, true),
+        },
+        CollisionCase {
+            name: "heads swap places",
+            snake_a: (&[(10, 5), (10, 4), (10, 3)], Direction::Right),
+            snake_b: (&[(10, 6), (10, 7), (10, 8)], Direction::Left),
+            same_team: false,
+            expected: (true, true),
+        },
+        CollisionCase {
+            name: "head runs into the side of a body",
+            snake_a: (&[(10, 5), (10, 4), (10, 3)], Direction::Right),
+            snake_b: (&[(9, 6), (10, 6), (11, 6)], Direction::Up),
+            same_team: false,
+            expected: (true, false),
+        },
+        CollisionCase {
+            name: "head follows a tail leaving its cell",
+            snake_a: (&[(10, 5), (10, 4), (10, 3)], Direction::Right),
+            snake_b: (&[(8, 6), (9, 6), (10, 6)], Direction::Up),
+            same_team: false,
+            expected: (false, false),
+        },
+        CollisionCase {
+            name: "snakes move side by side",
+            snake_a: (&[(10, 5), (10, 4), (10, 3)], Direction::Up),
+            snake_b: (&[(10, 6), (10, 7), (10, 8)], Direction::Up),
+            same_team: false,
+            expected: (false, false),
+        },
+        CollisionCase {
+            name: "teammates pass through each other",
+            snake_a: (&[(10, 5), (10, 4), (10, 3)], Direction::Right),
+            snake_b: (&[(10, 7), (10, 8), (10, 9)], Direction::Left),
+            same_team: true,
+            expected: (false, false),
+        },
+    ];
+
+    fn player(player_idx: usize, team_idx: usize, snake: (&[(usize, usize)], Direction)) -> Player {
+        let (body, direction) = snake;
+        let body_pos = body
+            .iter()
+            .map(|&(row, col)| Coordinate { row, col })
+            .collect();
+        Player::new(
+            player_idx,
+            team_idx,
+            SteeringMode::FourKeys,
+            Snake {
+                body_pos,
+                direction,
+            },
+        )
+    }
+
+    /// Moves both snakes by one tick, with `first` as player 1, and returns whether
+    /// `first` and `second` crashed.
+    fn crashes(
+        first: (&[(usize, usize)], Direction),
+        second: (&[(usize, usize)], Direction),
+        same_team: bool,
+    ) -> (bool, bool) {
+        let arena = Arena::new(20, 20);
+        let mut players = vec![
+            player(0, 0, first),
+            player(1, if same_team { 0 } else { 1 }, second),
+        ];
+        for player in &mut players {
+            move_snake(&mut player.snake.body_pos, player.snake.direction, &arena);
+        }
+
+        let crashed = find_crashed_players(&players, &arena, 0, true);
+        (crashed.contains(&0), crashed.contains(&1))
+    }
+
+    #[test]
+    fn collisions_are_fair() {
+        for case in &COLLISION_CASES {
+            assert_eq!(
+                crashes(case.snake_a, case.snake_b, case.same_team),
+                case.expected,
+                "{}",
+                case.name
+            );
+            // the outcome must not depend on which of the snakes is player 1
+            let (a_crashed, b_crashed) = case.expected;
+            assert_eq!(
+                crashes(case.snake_b, case.snake_a, case.same_team),
+                (b_crashed, a_crashed),
+                "{}, players swapped",
+                case.name
+            );
+        }
+    }
+
+    #[test]
+    fn steering_keys_are_distinct() {
+        let reserved = [KeyCode::Esc, KeyCode::Char('q'), SAVE_KEY];
+        let mut keys: Vec<KeyCode> = PLAYER_CONTROLS
+            .iter()
+            .flat_map(|controls| {
+                [
+                    controls.left,
+                    controls.right,
+                    controls.up,
+                    controls.down,
+                    controls.turn_left,
+                    controls.turn_right,
+                ]
+            })
+            .collect();
+        assert!(keys.iter().all(|key| !reserved.contains(key)));
+
+        let num_keys = keys.len();
+        keys.sort_by_key(|key| format!("{:?}", key));
+        keys.dedup();
+        assert_eq!(keys.len(), num_keys);
+    }
+
+    #[test]
+    fn crashed_players_are_no_obstacle() {
+        let arena = Arena::new(20, 20);
+        let mut players = vec![
+            player(0, 0, (&[(10, 8), (10, 7), (10, 6)], Direction::Right)),
+            player(1, 1, (&[(10, 7), (10, 8), (10, 9)], Direction::Left)),
+        ];
+        players[1].has_crashed = true;
 
-    for coord in snake_body {
-        screen_buffer.set_at(coord.row, coord.col, GameContent::SnakeBody(player_idx));
+        assert_eq!(find_crashed_players(&players, &arena, 0, false), vec![]);
     }
 }
@@ -0,0 +1,101 @@
+use std::io::{stdout, Result};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crossterm::{
+    cursor,
+    event::{
+        DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture,
+        KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+    ExecutableCommand,
+};
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::snake::GameSettings;
+
+/// Whether the game currently draws to the alternate screen, which has to be left again
+/// to bring back what was on the terminal before the game started.
+static IS_ALTERNATE_SCREEN_ACTIVE: AtomicBool = AtomicBool::new(false);
+static IS_MOUSE_CAPTURED: AtomicBool = AtomicBool::new(false);
+static IS_KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+/// Puts the terminal into the state the game needs and restores it when dropped,
+/// no matter if the game ends normally, with an error or with a panic.
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn new(settings: &GameSettings) -> Result<TerminalGuard> {
+        // the guard exists before the terminal is changed, so that a failure
+        // halfway through still gets cleaned up
+        let guard = TerminalGuard;
+        if settings.use_alternate_screen {
+            stdout().execute(EnterAlternateScreen)?;
+            IS_ALTERNATE_SCREEN_ACTIVE.store(true, Ordering::SeqCst);
+        }
+        enable_raw_mode()?;
+        // lets the game pause when the terminal loses the focus
+        stdout().execute(EnableFocusChange)?;
+        if settings.is_mouse_steering {
+            stdout().execute(EnableMouseCapture)?;
+            IS_MOUSE_CAPTURED.store(true, Ordering::SeqCst);
+        }
+        // with the kitty keyboard protocol, ESC is reported without delay and
+        // keys held together with modifiers are reported unambiguously
+        if settings.use_keyboard_enhancement && supports_keyboard_enhancement().unwrap_or(false) {
+            stdout().execute(PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KeyboardEnhancementFlags::REPORT_EVENT_TYPES,
+            ))?;
+            IS_KEYBOARD_ENHANCED.store(true, Ordering::SeqCst);
+        }
+        stdout().execute(cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore_terminal();
+    }
+}
+
+fn restore_terminal() -> Result<()> {
+    stdout().execute(cursor::Show)?;
+    stdout().execute(DisableFocusChange)?;
+    if IS_KEYBOARD_ENHANCED.swap(false, Ordering::SeqCst) {
+        stdout().execute(PopKeyboardEnhancementFlags)?;
+    }
+    if IS_MOUSE_CAPTURED.swap(false, Ordering::SeqCst) {
+        stdout().execute(DisableMouseCapture)?;
+    }
+    if IS_ALTERNATE_SCREEN_ACTIVE.swap(false, Ordering::SeqCst) {
+        stdout().execute(LeaveAlternateScreen)?;
+    }
+    disable_raw_mode()
+}
+
+/// Restores the terminal before a panic message is printed, otherwise the message
+/// would be garbled by raw mode or vanish together with the alternate screen.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        eprintln!("\nThe game crashed unexpectedly, sorry! Please report the message below.\n");
+        default_hook(info);
+    }));
+}
+
+/// Sets the flag when the process is asked to terminate, so that the game can shut
+/// down cleanly instead of being killed with the terminal still in raw mode.
+pub fn register_stop_signals(stop_requested: &Arc<AtomicBool>) -> Result<()> {
+    for signal in [SIGTERM, SIGINT] {
+        signal_hook::flag::register(signal, Arc::clone(stop_requested))?;
+    }
+    Ok(())
+}
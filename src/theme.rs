@@ -0,0 +1,383 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use crossterm::style::{available_color_count, Color};
+
+use crate::snake::MAX_PLAYERS;
+
+/// Number of colors portals take turns in, so that the pairs can be told apart.
+pub const NUM_PORTAL_COLORS: usize = 4;
+
+pub const BUILTIN_THEME_NAMES: [&str; 4] =
+    ["default", "high-contrast", "colorblind-safe", "monochrome"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub snake_heads: [Color; MAX_PLAYERS],
+    pub snake_bodies: [Color; MAX_PLAYERS],
+    /// human readable name of each team color, shown on the start screen
+    pub team_color_names: [String; MAX_PLAYERS],
+    pub food: Color,
+    pub golden_food: Color,
+    /// both ends of a portal share one of these colors, picked by the index of the portal
+    pub portals: [Color; NUM_PORTAL_COLORS],
+    pub hazard: Color,
+    pub border: Color,
+    pub obstacle: Color,
+    pub background: Color,
+    pub text: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            name: "default".to_string(),
+            snake_heads: [
+                Color::DarkGreen,
+                Color::DarkYellow,
+                Color::DarkCyan,
+                Color::DarkMagenta,
+            ],
+            snake_bodies: [Color::Green, Color::Yellow, Color::Cyan, Color::Magenta],
+            team_color_names: color_names(["green", "yellow", "cyan", "magenta"]),
+            food: Color::Red,
+            golden_food: rgb(255, 200, 0),
+            portals: [
+                rgb(160, 90, 255),
+                rgb(255, 130, 180),
+                rgb(90, 150, 255),
+                rgb(170, 255, 140),
+            ],
+            hazard: Color::White,
+            border: Color::DarkBlue,
+            obstacle: Color::DarkGrey,
+            background: Color::Black,
+            text: Color::White,
+        }
+    }
+}
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Theme::default()),
+            "high-contrast" => Some(Theme {
+                name: name.to_string(),
+                snake_heads: [Color::White; MAX_PLAYERS],
+                hazard: Color::Blue,
+                border: Color::White,
+                obstacle: Color::Grey,
+                ..Theme::default()
+            }),
+            // based on the Okabe-Ito palette, which stays distinguishable for all common
+            // types of color vision deficiency
+            "colorblind-safe" => Some(Theme {
+                name: name.to_string(),
+                snake_heads: [
+                    rgb(0, 114, 178),
+                    rgb(213, 94, 0),
+                    rgb(0, 110, 80),
+                    rgb(150, 80, 120),
+                ],
+                snake_bodies: [
+                    rgb(86, 180, 233),
+                    rgb(230, 159, 0),
+                    rgb(0, 158, 115),
+                    rgb(204, 121, 167),
+                ],
+                team_color_names: color_names(["blue", "orange", "green", "purple"]),
+                food: rgb(240, 228, 66),
+                golden_food: Color::White,
+                // magenta of the IBM palette, far from the other colors in every type of
+                // color vision deficiency
+                hazard: rgb(220, 38, 127),
+                border: rgb(90, 90, 90),
+                obstacle: rgb(150, 150, 150),
+                ..Theme::default()
+            }),
+            "monochrome" => Some(Theme {
+                name: name.to_string(),
+                snake_heads: [Color::White; MAX_PLAYERS],
+                snake_bodies: [Color::Grey; MAX_PLAYERS],
+                team_color_names: color_names(["white"; MAX_PLAYERS]),
+                // every kind of tile gets a grey level of its own
+                food: rgb(208, 208, 208),
+                golden_food: rgb(228, 228, 228),
+                portals: [
+                    rgb(148, 148, 148),
+                    rgb(108, 108, 108),
+                    rgb(68, 68, 68),
+                    rgb(48, 48, 48),
+                ],
+                hazard: rgb(168, 168, 168),
+                border: Color::DarkGrey,
+                obstacle: rgb(88, 88, 88),
+                ..Theme::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Replaces colors the terminal cannot display by their closest match.
+    pub fn adapted_to_terminal(mut self) -> Theme {
+        let color_count = available_color_count();
+        let adapt = |color: &mut Color| *color = downgrade_color(*color, color_count);
+
+        self.snake_heads.iter_mut().for_each(adapt);
+        self.snake_bodies.iter_mut().for_each(adapt);
+        self.portals.iter_mut().for_each(adapt);
+        for color in [
+            &mut self.food,
+            &mut self.golden_food,
+            &mut self.hazard,
+            &mut self.border,
+            &mut self.obstacle,
+            &mut self.background,
+            &mut self.text,
+        ] {
+            adapt(color);
+        }
+        self
+    }
+}
+
+const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+fn color_names(names: [&str; MAX_PLAYERS]) -> [String; MAX_PLAYERS] {
+    names.map(|name| name.to_string())
+}
+
+/// Loads all themes defined in a theme file, which looks like this:
+///
+/// ```text
+/// # unspecified colors are taken from the default theme
+/// [ocean]
+/// head1 = #0072b2
+/// body1 = rgb(86, 180, 233)
+/// name1 = blue
+/// food = yellow
+/// ```
+pub fn load_themes(path: &Path) -> Result<Vec<Theme>> {
+    let content = fs::read_to_string(path)?;
+    let mut themes: Vec<Theme> = vec![];
+
+    for (line_idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let invalid = |msg: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{}:{}: {}", path.display(), line_idx + 1, msg),
+            )
+        };
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            themes.push(Theme {
+                name: name.trim().to_string(),
+                ..Theme::default()
+            });
+            continue;
+        }
+
+        let theme = themes
+            .last_mut()
+            .ok_or_else(|| invalid("expected a [theme name] first".to_string()))?;
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("expected key = value, got '{}'", line)))?;
+        let (key, value) = (key.trim(), value.trim());
+
+        if let Some(team_idx) = numbered_key_index(key, "name", MAX_PLAYERS) {
+            theme.team_color_names[team_idx] = value.to_string();
+            continue;
+        }
+
+        let color =
+            parse_color(value).ok_or_else(|| invalid(format!("unknown color '{}'", value)))?;
+        match key {
+            "food" => theme.food = color,
+            "golden_food" => theme.golden_food = color,
+            "portal" => theme.portals = [color; NUM_PORTAL_COLORS],
+            "hazard" => theme.hazard = color,
+            "border" => theme.border = color,
+            "obstacle" => theme.obstacle = color,
+            "background" => theme.background = color,
+            "text" => theme.text = color,
+            _ => {
+                if let Some(team_idx) = numbered_key_index(key, "head", MAX_PLAYERS) {
+                    theme.snake_heads[team_idx] = color;
+                } else if let Some(team_idx) = numbered_key_index(key, "body", MAX_PLAYERS) {
+                    theme.snake_bodies[team_idx] = color;
+                } else if let Some(portal_idx) =
+                    numbered_key_index(key, "portal", NUM_PORTAL_COLORS)
+                {
+                    theme.portals[portal_idx] = color;
+                } else {
+                    return Err(invalid(format!("unknown key '{}'", key)));
+                }
+            }
+        }
+    }
+    Ok(themes)
+}
+
+/// Parses keys like "head1" into the zero based index, which is below `count`.
+fn numbered_key_index(key: &str, prefix: &str, count: usize) -> Option<usize> {
+    let nr: usize = key.strip_prefix(prefix)?.parse().ok()?;
+    (1..=count).contains(&nr).then_some(nr - 1)
+}
+
+/// Parses named colors like "dark_green", "#rrggbb" and "rgb(r, g, b)".
+pub fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |idx: usize| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+
+    if let Some(channels) = value.strip_prefix("rgb(").and_then(|v| v.strip_suffix(')')) {
+        let channels = channels
+            .split(',')
+            .map(|c| c.trim().parse::<u8>().ok())
+            .collect::<Option<Vec<u8>>>()?;
+        return match channels.as_slice() {
+            [r, g, b] => Some(Color::Rgb {
+                r: *r,
+                g: *g,
+                b: *b,
+            }),
+            _ => None,
+        };
+    }
+
+    Color::try_from(value).ok()
+}
+
+/// Maps true color values to the 256 color palette or to the 16 basic colors,
+/// depending on how many colors the terminal supports.
+fn downgrade_color(color: Color, color_count: u16) -> Color {
+    let (r, g, b) = match color {
+        Color::Rgb { r, g, b } => (r, g, b),
+        _ => return color,
+    };
+
+    if color_count == u16::MAX {
+        color
+    } else if color_count >= 256 && r == g && g == b {
+        // the grey ramp at the end of the 256 color palette has finer steps than the
+        // color cube, from 8 to 238 in steps of 10
+        match r {
+            0..=3 => Color::AnsiValue(16),
+            244..=255 => Color::AnsiValue(231),
+            _ => Color::AnsiValue(232 + ((r - 3) / 10).min(23)),
+        }
+    } else if color_count >= 256 {
+        // the 6x6x6 color cube of the 256 color palette starts at index 16
+        let to_cube = |c: u8| ((c as u16 * 5 + 127) / 255) as u8;
+        Color::AnsiValue(16 + 36 * to_cube(r) + 6 * to_cube(g) + to_cube(b))
+    } else {
+        let basic_colors = [
+            (Color::Black, (0, 0, 0)),
+            (Color::DarkRed, (128, 0, 0)),
+            (Color::DarkGreen, (0, 128, 0)),
+            (Color::DarkYellow, (128, 128, 0)),
+            (Color::DarkBlue, (0, 0, 128)),
+            (Color::DarkMagenta, (128, 0, 128)),
+            (Color::DarkCyan, (0, 128, 128)),
+            (Color::Grey, (192, 192, 192)),
+            (Color::DarkGrey, (128, 128, 128)),
+            (Color::Red, (255, 0, 0)),
+            (Color::Green, (0, 255, 0)),
+            (Color::Yellow, (255, 255, 0)),
+            (Color::Blue, (0, 0, 255)),
+            (Color::Magenta, (255, 0, 255)),
+            (Color::Cyan, (0, 255, 255)),
+            (Color::White, (255, 255, 255)),
+        ];
+        let distance = |(br, bg, bb): (i32, i32, i32)| {
+            (br - r as i32).pow(2) + (bg - g as i32).pow(2) + (bb - b as i32).pow(2)
+        };
+        basic_colors
+            .iter()
+            .min_by_key(|(_, basic_rgb)| distance(*basic_rgb))
+            .map(|(basic_color, _)| *basic_color)
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Colors of everything drawn in the arena.
+    fn tile_colors(theme: &Theme) -> Vec<Color> {
+        let mut colors: Vec<Color> = theme
+            .snake_heads
+            .iter()
+            .chain(&theme.snake_bodies)
+            .chain(&theme.portals)
+            .copied()
+            .collect();
+        colors.extend([
+            theme.food,
+            theme.golden_food,
+            theme.hazard,
+            theme.border,
+            theme.obstacle,
+            theme.background,
+        ]);
+        colors
+    }
+
+    #[test]
+    fn builtin_themes_tell_items_apart() {
+        for name in BUILTIN_THEME_NAMES {
+            let theme = Theme::builtin(name).unwrap();
+            // a deadly hazard must never look like anything else
+            let num_hazard_colored = tile_colors(&theme)
+                .iter()
+                .filter(|&&color| color == theme.hazard)
+                .count();
+            assert_eq!(num_hazard_colored, 1, "{}: hazard", name);
+
+            let mut items = vec![theme.food, theme.golden_food, theme.hazard];
+            items.extend(theme.portals);
+            for (idx, item) in items.iter().enumerate() {
+                assert!(!items[idx + 1..].contains(item), "{}: {:?}", name, item);
+                assert!(!theme.snake_heads.contains(item), "{}: {:?}", name, item);
+            }
+            // portals are no part of the border
+            assert!(!theme.portals.contains(&theme.border), "{}: portal", name);
+        }
+    }
+
+    #[test]
+    fn greys_keep_their_level_with_256_colors() {
+        let greys: Vec<Color> = [8, 50, 88, 128, 168, 208, 238]
+            .iter()
+            .map(|&level| downgrade_color(rgb(level, level, level), 256))
+            .collect();
+        for (idx, grey) in greys.iter().enumerate() {
+            assert!(!greys[idx + 1..].contains(grey), "{:?}", grey);
+        }
+        assert_eq!(downgrade_color(rgb(0, 0, 0), 256), Color::AnsiValue(16));
+        assert_eq!(
+            downgrade_color(rgb(255, 255, 255), 256),
+            Color::AnsiValue(231)
+        );
+    }
+}